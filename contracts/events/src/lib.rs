@@ -0,0 +1,220 @@
+//! NEP-297 structured event logs shared by the token and market contracts.
+//!
+//! Every event is logged as `EVENT_JSON:{"standard":...,"version":...,"event":...,"data":[...]}`
+//! so indexers can parse activity without matching on free-form log strings.
+use std::fmt;
+
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::Serialize;
+use near_sdk::{env, AccountId};
+
+pub const EVENT_JSON_PREFIX: &str = "EVENT_JSON:";
+
+pub const NFT_STANDARD_NAME: &str = "nep171";
+pub const NFT_METADATA_SPEC_VERSION: &str = "1.0.0";
+
+pub const MARKET_STANDARD_NAME: &str = "nft_launcher_market";
+pub const MARKET_VERSION: &str = "1.0.0";
+
+pub const GUEST_STANDARD_NAME: &str = "nft_launcher_guests";
+pub const GUEST_VERSION: &str = "1.0.0";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventLog<T: Serialize> {
+    pub standard: &'static str,
+    pub version: &'static str,
+    #[serde(flatten)]
+    pub event: T,
+}
+
+impl<T: Serialize> fmt::Display for EventLog<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!(
+            "{}{}",
+            EVENT_JSON_PREFIX,
+            near_sdk::serde_json::to_string(self).map_err(|_| fmt::Error)?
+        ))
+    }
+}
+
+impl<T: Serialize> EventLog<T> {
+    pub fn emit(&self) {
+        env::log(self.to_string().as_bytes());
+    }
+}
+
+/// token contract events (NEP-171)
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum NftEventKind {
+    NftMint(Vec<NftMintLog>),
+    NftTransfer(Vec<NftTransferLog>),
+    NftBurn(Vec<NftBurnLog>),
+}
+
+impl NftEventKind {
+    pub fn emit(self) {
+        EventLog {
+            standard: NFT_STANDARD_NAME,
+            version: NFT_METADATA_SPEC_VERSION,
+            event: self,
+        }
+        .emit();
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMintLog {
+    pub owner_id: AccountId,
+    pub token_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftTransferLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<AccountId>,
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    pub token_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftBurnLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<AccountId>,
+    pub owner_id: AccountId,
+    pub token_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// token contract guest events, kept out of the nep171 standard
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum GuestEventKind {
+    GuestWithdrawn(Vec<GuestWithdrawLog>),
+}
+
+impl GuestEventKind {
+    pub fn emit(self) {
+        EventLog {
+            standard: GUEST_STANDARD_NAME,
+            version: GUEST_VERSION,
+            event: self,
+        }
+        .emit();
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GuestWithdrawLog {
+    pub account_id: AccountId,
+    pub receiver_id: AccountId,
+    /// what was sent, the withdraw fee is already taken out
+    pub amount: U128,
+    pub token_ids: Vec<String>,
+}
+
+/// market contract events
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum MarketEventKind {
+    SaleCreated(Vec<SaleLog>),
+    SaleUpdated(Vec<SaleLog>),
+    SaleRemoved(Vec<SaleLog>),
+    SaleCompleted(Vec<SaleCompletedLog>),
+    AuctionBid(Vec<AuctionBidLog>),
+    OfferCreated(Vec<OfferLog>),
+    OfferRemoved(Vec<OfferLog>),
+    OfferAccepted(Vec<SaleCompletedLog>),
+    CollectionOfferCreated(Vec<CollectionOfferLog>),
+    CollectionOfferRemoved(Vec<CollectionOfferLog>),
+    CollectionOfferFilled(Vec<SaleCompletedLog>),
+}
+
+impl MarketEventKind {
+    pub fn emit(self) {
+        EventLog {
+            standard: MARKET_STANDARD_NAME,
+            version: MARKET_VERSION,
+            event: self,
+        }
+        .emit();
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleLog {
+    pub nft_contract_id: AccountId,
+    pub token_id: String,
+    pub owner_id: AccountId,
+    pub price: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleCompletedLog {
+    pub nft_contract_id: AccountId,
+    pub token_id: String,
+    pub owner_id: AccountId,
+    pub buyer_id: AccountId,
+    pub price: U128,
+    /// the NEP-141 token `price` is denominated in, NEAR when absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>,
+    /// marketplace fee taken out of `price`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee: Option<U128>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionBidLog {
+    pub nft_contract_id: AccountId,
+    pub token_id: String,
+    pub owner_id: AccountId,
+    pub bidder_id: AccountId,
+    pub bid: U128,
+    pub end_at: U64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferLog {
+    pub nft_contract_id: AccountId,
+    pub token_id: String,
+    pub bidder_id: AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<U64>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CollectionOfferLog {
+    pub nft_contract_id: AccountId,
+    pub bidder_id: AccountId,
+    pub price_per_token: U128,
+    pub quantity: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<U64>,
+}
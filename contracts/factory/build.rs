@@ -0,0 +1,49 @@
+//! Fails the factory build when the embedded nft-simple wasm is older than the nft-simple sources,
+//! ./build.sh rebuilds it first
+
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+const NFT_SIMPLE_WASM: &str = "../nft-simple/res/nft_simple.wasm";
+/// nft-simple and the crates it builds from
+const NFT_SIMPLE_SOURCES: &[&str] = &[
+    "../nft-simple/src",
+    "../nft-simple/Cargo.toml",
+    "../events/src",
+    "../events/Cargo.toml",
+];
+
+fn newest_modified(path: &Path) -> Option<SystemTime> {
+    let metadata = fs::metadata(path).ok()?;
+    if !metadata.is_dir() {
+        return metadata.modified().ok();
+    }
+    fs::read_dir(path)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| newest_modified(&entry.path()))
+        .max()
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", NFT_SIMPLE_WASM);
+    for source in NFT_SIMPLE_SOURCES {
+        println!("cargo:rerun-if-changed={}", source);
+    }
+
+    let wasm_modified = fs::metadata(NFT_SIMPLE_WASM)
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or_else(|_| panic!("{} is missing, run ./build.sh", NFT_SIMPLE_WASM));
+    let sources_modified = NFT_SIMPLE_SOURCES
+        .iter()
+        .filter_map(|source| newest_modified(Path::new(source)))
+        .max();
+    if let Some(sources_modified) = sources_modified {
+        assert!(
+            sources_modified <= wasm_modified,
+            "{} is older than the nft-simple sources, run ./build.sh so the factory doesn't deploy a stale contract",
+            NFT_SIMPLE_WASM
+        );
+    }
+}
@@ -0,0 +1,8 @@
+#!/bin/bash
+set -e
+
+# embeds ../nft-simple/res/nft_simple.wasm, rebuild it so collections get the current nft-simple
+(cd ../nft-simple && ./build.sh)
+RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release
+mkdir -p ../../out
+cp target/wasm32-unknown-unknown/release/*.wasm ../../out/factory.wasm
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::json_types::{U128, U64, ValidAccountId};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::json;
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, Balance, Gas, PanicOnDefault, Promise, PromiseResult};
+
+#[global_allocator]
+static ALLOC: near_sdk::wee_alloc::WeeAlloc<'_> = near_sdk::wee_alloc::WeeAlloc::INIT;
+
+/// rebuilt by build.sh before the factory, build.rs fails the build if it is older than the nft-simple sources
+const NFT_SIMPLE_CODE: &[u8] = include_bytes!("../../nft-simple/res/nft_simple.wasm");
+const NFT_METADATA_SPEC: &str = "nft-1.0.0";
+const GAS_FOR_NEW: Gas = 50_000_000_000_000;
+const GAS_FOR_ON_COLLECTION_CREATED: Gas = 20_000_000_000_000;
+const NO_DEPOSIT: Balance = 0;
+/// on top of the code, covers the collection's initial state
+const EXTRA_STORAGE_IN_BYTES: u128 = 10_000;
+const DEFAULT_PAGE_LIMIT: u64 = 50;
+
+/// mirrors nft-simple's MintingPolicy, passed through to `new`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum MintingPolicy {
+    Open,
+    OwnerOnly,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Collection {
+    pub collection_id: AccountId,
+    pub creator_id: AccountId,
+    pub name: String,
+    pub symbol: String,
+    pub minting_policy: MintingPolicy,
+    pub created_at: U64,
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Contract {
+    pub owner_id: AccountId,
+    pub collections: UnorderedMap<AccountId, Collection>,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(owner_id: ValidAccountId) -> Self {
+        assert!(!env::state_exists(), "Already initialized");
+        Self {
+            owner_id: owner_id.into(),
+            collections: UnorderedMap::new(b"c".to_vec()),
+        }
+    }
+
+    /// deploys nft-simple to <name>.<factory> with the creator as owner_id
+    /// the attached deposit becomes the collection's balance and is refunded if anything fails
+    #[payable]
+    pub fn create_collection(
+        &mut self,
+        name: String,
+        symbol: String,
+        royalties: Option<HashMap<AccountId, u32>>,
+        minting_policy: Option<MintingPolicy>,
+    ) -> Promise {
+        let collection_id = format!("{}.{}", name, env::current_account_id());
+        assert!(
+            !name.contains('.') && env::is_valid_account_id(collection_id.as_bytes()),
+            "Invalid collection name {}", name
+        );
+        assert!(self.collections.get(&collection_id).is_none(), "Collection already exists");
+        let deposit = env::attached_deposit();
+        let min_deposit = min_collection_deposit();
+        assert!(deposit >= min_deposit, "Must attach at least {} to create a collection", min_deposit);
+
+        let creator_id = env::predecessor_account_id();
+        let minting_policy = minting_policy.unwrap_or(MintingPolicy::Open);
+        let args = json!({
+            "owner_id": creator_id,
+            "metadata": {
+                "spec": NFT_METADATA_SPEC,
+                "name": name,
+                "symbol": symbol,
+            },
+            "default_royalty": royalties,
+            "minting_policy": minting_policy,
+        });
+
+        Promise::new(collection_id.clone())
+            .create_account()
+            .transfer(deposit)
+            .deploy_contract(NFT_SIMPLE_CODE.to_vec())
+            .function_call(
+                b"new".to_vec(),
+                args.to_string().into_bytes(),
+                NO_DEPOSIT,
+                GAS_FOR_NEW,
+            )
+            .then(ext_self::on_collection_created(
+                Collection {
+                    collection_id,
+                    creator_id,
+                    name,
+                    symbol,
+                    minting_policy,
+                    created_at: env::block_timestamp().into(),
+                },
+                deposit.into(),
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                GAS_FOR_ON_COLLECTION_CREATED,
+            ))
+    }
+
+    /// self callback
+
+    /// a failed batch never creates the account and its deposit comes back to the factory
+    pub fn on_collection_created(&mut self, collection: Collection, deposit: U128) -> bool {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Method is private"
+        );
+        let success = is_promise_success();
+        if success {
+            self.collections.insert(&collection.collection_id, &collection);
+        } else {
+            env::log(format!("Refunding {} to @{}", deposit.0, collection.creator_id).as_bytes());
+            Promise::new(collection.creator_id).transfer(deposit.into());
+        }
+        success
+    }
+
+    /// view methods
+
+    pub fn get_collection(&self, collection_id: ValidAccountId) -> Option<Collection> {
+        self.collections.get(collection_id.as_ref())
+    }
+
+    pub fn get_collections(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Collection> {
+        let start = u128::from(from_index.unwrap_or(U128(0))) as u64;
+        let values = self.collections.values_as_vector();
+        (start..std::cmp::min(start + limit.unwrap_or(DEFAULT_PAGE_LIMIT), values.len()))
+            .map(|index| values.get(index).unwrap())
+            .collect()
+    }
+
+    pub fn get_collections_supply(&self) -> U64 {
+        self.collections.len().into()
+    }
+
+    pub fn get_min_collection_deposit(&self) -> U128 {
+        min_collection_deposit().into()
+    }
+}
+
+fn min_collection_deposit() -> Balance {
+    (NFT_SIMPLE_CODE.len() as u128 + EXTRA_STORAGE_IN_BYTES) * env::storage_byte_cost()
+}
+
+#[ext_contract(ext_self)]
+trait ExtContract {
+    fn on_collection_created(&mut self, collection: Collection, deposit: U128) -> bool;
+}
+
+fn is_promise_success() -> bool {
+    assert_eq!(
+        env::promise_results_count(),
+        1,
+        "Contract expected a result on the callback"
+    );
+    match env::promise_result(0) {
+        PromiseResult::Successful(_) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,181 @@
+use crate::*;
+
+/// English auction, the sale's `price` is the reserve price
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Auction {
+    pub min_increment: U128,
+    pub start_at: U64,
+    pub end_at: U64,
+    /// bids within this many nanoseconds of `end_at` push `end_at` out to now + extension
+    pub extension: U64,
+    pub bidder_id: Option<AccountId>,
+    pub bid: U128,
+}
+
+/// what a seller passes in the `OnApprovalMsg` to list an auction
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionArgs {
+    pub min_increment: U128,
+    pub start_at: Option<U64>,
+    pub end_at: U64,
+    pub extension: Option<U64>,
+}
+
+impl From<AuctionArgs> for Auction {
+    fn from(args: AuctionArgs) -> Self {
+        let now = env::block_timestamp();
+        let start_at = args.start_at.map(u64::from).unwrap_or(now);
+        let end_at = u64::from(args.end_at);
+        assert!(end_at > start_at && end_at > now, "Auction must end in the future");
+        assert!(args.min_increment.0 > 0, "Minimum increment must be greater than 0");
+        Self {
+            min_increment: args.min_increment,
+            start_at: start_at.into(),
+            end_at: end_at.into(),
+            extension: args.extension.unwrap_or(U64(DEFAULT_AUCTION_EXTENSION)),
+            bidder_id: None,
+            bid: U128(0),
+        }
+    }
+}
+
+impl Auction {
+    /// the lowest bid that can be placed right now
+    pub fn min_bid(&self, reserve_price: Balance) -> Balance {
+        if self.bidder_id.is_some() {
+            self.bid.0 + self.min_increment.0
+        } else {
+            reserve_price
+        }
+    }
+}
+
+/// Dutch auction, the price declines from `start_price` (the sale's listed price) to `end_price` between `start_at` and `end_at`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DutchAuction {
+    pub start_price: U128,
+    pub end_price: U128,
+    pub start_at: U64,
+    pub end_at: U64,
+    /// the price drops once per step (in nanoseconds) instead of continuously
+    pub step: Option<U64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DutchAuctionArgs {
+    pub end_price: U128,
+    pub start_at: Option<U64>,
+    pub end_at: U64,
+    pub step: Option<U64>,
+}
+
+impl DutchAuction {
+    pub fn new(args: DutchAuctionArgs, start_price: U128) -> Self {
+        let now = env::block_timestamp();
+        let start_at = args.start_at.map(u64::from).unwrap_or(now);
+        let end_at = u64::from(args.end_at);
+        assert!(end_at > start_at && end_at > now, "Auction must end in the future");
+        assert!(args.end_price.0 < start_price.0, "End price must be lower than the start price");
+        if let Some(step) = args.step {
+            assert!(step.0 > 0 && step.0 <= end_at - start_at, "Step must fit in the auction duration");
+        }
+        Self {
+            start_price,
+            end_price: args.end_price,
+            start_at: start_at.into(),
+            end_at: end_at.into(),
+            step: args.step,
+        }
+    }
+
+    pub fn current_price(&self) -> Balance {
+        let start_price = self.start_price.0;
+        let now = env::block_timestamp();
+        if now <= self.start_at.0 {
+            return start_price;
+        }
+        if now >= self.end_at.0 {
+            return self.end_price.0;
+        }
+        let duration = Balance::from(self.end_at.0 - self.start_at.0);
+        let mut elapsed = now - self.start_at.0;
+        if let Some(step) = self.step {
+            elapsed -= elapsed % step.0;
+        }
+        let elapsed = Balance::from(elapsed);
+        // split so the multiplication can't overflow for large prices
+        let range = start_price - self.end_price.0;
+        let decay = range / duration * elapsed + range % duration * elapsed / duration;
+        start_price - decay
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// escrows the attached deposit as the new highest bid, the previous bidder is refunded
+    #[payable]
+    pub fn bid(&mut self, token_contract_id: ValidAccountId, token_id: String) {
+        let contract_id: AccountId = token_contract_id.into();
+        let contract_and_token_id = format!("{}:{}", contract_id, token_id);
+        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        assert_eq!(sale.processing, false, "Sale is currently in progress");
+        let bidder_id = env::predecessor_account_id();
+        assert_ne!(bidder_id, sale.owner_id, "Cannot bid on your own sale");
+        let price = sale.price.0;
+        let auction = sale.auction.as_mut().expect("Not an auction");
+
+        let now = env::block_timestamp();
+        assert!(now >= auction.start_at.0, "Auction has not started");
+        assert!(now < auction.end_at.0, "Auction has ended");
+        let amount = env::attached_deposit();
+        let min_bid = auction.min_bid(price);
+        assert!(amount >= min_bid, "Bid must be at least {}", min_bid);
+
+        if let Some(previous_bidder_id) = auction.bidder_id.take() {
+            Promise::new(previous_bidder_id).transfer(auction.bid.0);
+        }
+        auction.bidder_id = Some(bidder_id.clone());
+        auction.bid = amount.into();
+        // anti-sniping, a late bid gives everyone else time to respond
+        if auction.end_at.0 - now < auction.extension.0 {
+            auction.end_at = (now + auction.extension.0).into();
+        }
+        let end_at = auction.end_at;
+        self.sales.insert(&contract_and_token_id, &sale);
+
+        MarketEventKind::AuctionBid(vec![AuctionBidLog {
+            nft_contract_id: contract_id,
+            token_id,
+            owner_id: sale.owner_id,
+            bidder_id,
+            bid: amount.into(),
+            end_at,
+        }])
+        .emit();
+    }
+
+    /// anyone can close an auction once it ended, the winning bid buys the token
+    pub fn settle_auction(&mut self, token_contract_id: ValidAccountId, token_id: String) -> Option<Promise> {
+        let contract_id: AccountId = token_contract_id.into();
+        let contract_and_token_id = format!("{}:{}", contract_id, token_id);
+        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        assert_eq!(sale.processing, false, "Sale is currently in progress");
+        let auction = sale.auction.as_ref().expect("Not an auction");
+        assert!(env::block_timestamp() >= auction.end_at.0, "Auction has not ended");
+
+        if let Some(bidder_id) = auction.bidder_id.clone() {
+            let bid = auction.bid;
+            sale.processing = true;
+            self.sales.insert(&contract_and_token_id, &sale);
+            Some(self.internal_purchase(contract_id, token_id, &sale, bidder_id, bid, None))
+        } else {
+            // no bids, the listing is over
+            self.internal_remove_sale(contract_id, token_id);
+            None
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use crate::*;
+
+/// escrowed offer for any `quantity` tokens of a contract at `price_per_token`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CollectionOffer {
+    pub nft_contract_id: AccountId,
+    pub bidder_id: AccountId,
+    pub price_per_token: U128,
+    /// tokens still wanted, fills in flight are counted in `pending`
+    pub quantity: u32,
+    pub pending: u32,
+    pub expires_at: Option<U64>,
+}
+
+impl CollectionOffer {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map(|expires_at| env::block_timestamp() >= expires_at.0).unwrap_or(false)
+    }
+
+    /// what is left in escrow for unfilled tokens
+    fn escrow(&self) -> Balance {
+        self.price_per_token.0 * Balance::from(self.quantity)
+    }
+
+    fn log(&self) -> CollectionOfferLog {
+        CollectionOfferLog {
+            nft_contract_id: self.nft_contract_id.clone(),
+            bidder_id: self.bidder_id.clone(),
+            price_per_token: self.price_per_token,
+            quantity: self.quantity,
+            expires_at: self.expires_at,
+        }
+    }
+}
+
+/// msg a holder passes to `nft_approve` to sell a token to a collection offer
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FillCollectionOfferMsg {
+    pub fill_collection_offer: FillCollectionOffer,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FillCollectionOffer {
+    pub bidder_id: AccountId,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// the attached deposit must be exactly price_per_token * quantity
+    /// making a new collection offer on the same contract replaces (and refunds) the previous one
+    #[payable]
+    pub fn make_collection_offer(
+        &mut self,
+        token_contract_id: ValidAccountId,
+        price_per_token: U128,
+        quantity: u32,
+        expires_at: Option<U64>,
+    ) {
+        assert!(quantity > 0, "Quantity must be greater than 0");
+        assert!(price_per_token.0 >= MIN_OFFER_AMOUNT, "Offers must be at least 0.1 NEAR per token");
+        let escrow = price_per_token.0.checked_mul(Balance::from(quantity)).expect("Escrow overflow");
+        assert_eq!(env::attached_deposit(), escrow, "Must attach exactly {} for {} tokens", escrow, quantity);
+        if let Some(expires_at) = expires_at {
+            assert!(expires_at.0 > env::block_timestamp(), "Offer must expire in the future");
+        }
+        let contract_id: AccountId = token_contract_id.into();
+        let bidder_id = env::predecessor_account_id();
+        let id = collection_offer_id(&contract_id, &bidder_id);
+        if let Some(previous_offer) = self.collection_offers.get(&id) {
+            assert_eq!(previous_offer.pending, 0, "Offer is currently in progress");
+            Promise::new(bidder_id.clone()).transfer(previous_offer.escrow());
+        }
+
+        let offer = CollectionOffer {
+            nft_contract_id: contract_id.clone(),
+            bidder_id: bidder_id.clone(),
+            price_per_token,
+            quantity,
+            pending: 0,
+            expires_at,
+        };
+        self.collection_offers.insert(&id, &offer);
+        let mut bidder_ids = self.collection_offers_by_contract.get(&contract_id).unwrap_or_else(|| {
+            UnorderedSet::new(unique_prefix(b'C', &contract_id))
+        });
+        bidder_ids.insert(&bidder_id);
+        self.collection_offers_by_contract.insert(&contract_id, &bidder_ids);
+
+        MarketEventKind::CollectionOfferCreated(vec![offer.log()]).emit();
+    }
+
+    /// refunds the escrow for the remaining quantity
+    pub fn cancel_collection_offer(&mut self, token_contract_id: ValidAccountId) {
+        let contract_id: AccountId = token_contract_id.into();
+        let bidder_id = env::predecessor_account_id();
+        let offer = self.collection_offers.get(&collection_offer_id(&contract_id, &bidder_id)).expect("No offer");
+        assert_eq!(offer.pending, 0, "Offer is currently in progress");
+        Promise::new(bidder_id.clone()).transfer(offer.escrow());
+        self.internal_remove_collection_offer(&contract_id, &bidder_id);
+        MarketEventKind::CollectionOfferRemoved(vec![offer.log()]).emit();
+    }
+
+    /// self callback
+
+    pub fn nft_resolve_collection_offer(
+        &mut self,
+        token_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        bidder_id: AccountId,
+        fee: U128,
+    ) -> bool {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Method is private"
+        );
+        let id = collection_offer_id(&token_contract_id, &bidder_id);
+        let mut offer = self.collection_offers.get(&id).expect("No offer");
+        offer.pending -= 1;
+        let success = if let PromiseResult::Successful(value) = env::promise_result(0) {
+            let price = offer.price_per_token;
+            self.internal_settle_offer(&token_contract_id, &token_id, &owner_id, &value, price.0, fee.0);
+
+            MarketEventKind::CollectionOfferFilled(vec![SaleCompletedLog {
+                nft_contract_id: token_contract_id.clone(),
+                token_id,
+                owner_id,
+                buyer_id: bidder_id.clone(),
+                price,
+                ft_token_id: None,
+                fee: Some(fee),
+            }])
+            .emit();
+            true
+        } else {
+            // transfer was rejected, the escrow for this token is still held
+            offer.quantity += 1;
+            false
+        };
+        if offer.quantity == 0 && offer.pending == 0 {
+            self.internal_remove_collection_offer(&token_contract_id, &bidder_id);
+        } else {
+            self.collection_offers.insert(&id, &offer);
+        }
+        success
+    }
+
+    /// view methods
+
+    pub fn get_collection_offer(&self, token_contract_id: ValidAccountId, bidder_id: ValidAccountId) -> Option<CollectionOffer> {
+        self.collection_offers.get(&collection_offer_id(token_contract_id.as_ref(), bidder_id.as_ref()))
+    }
+
+    pub fn get_collection_offers(&self, token_contract_id: ValidAccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<CollectionOffer> {
+        let contract_id: AccountId = token_contract_id.into();
+        let bidder_ids = if let Some(bidder_ids) = self.collection_offers_by_contract.get(&contract_id) {
+            bidder_ids
+        } else {
+            return vec![];
+        };
+        let bidder_ids = bidder_ids.as_vector();
+        let start = u128::from(from_index.unwrap_or(U128(0))) as u64;
+        (start..std::cmp::min(start + limit.unwrap_or(DEFAULT_PAGE_LIMIT), bidder_ids.len()))
+            .filter_map(|index| self.collection_offers.get(&collection_offer_id(&contract_id, &bidder_ids.get(index).unwrap())))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// called from nft_on_approve, reserves one unit of the offer while the token is transferred
+    pub(crate) fn internal_fill_collection_offer(
+        &mut self,
+        contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        bidder_id: AccountId,
+    ) -> Promise {
+        let id = collection_offer_id(&contract_id, &bidder_id);
+        let mut offer = self.collection_offers.get(&id).expect("No offer");
+        assert!(offer.quantity > 0, "Offer is already filled");
+        assert!(!offer.is_expired(), "Offer has expired");
+        assert_ne!(owner_id, bidder_id, "Cannot fill your own offer");
+        offer.quantity -= 1;
+        offer.pending += 1;
+        self.collection_offers.insert(&id, &offer);
+
+        let fee = self.internal_fee(&contract_id, offer.price_per_token.0);
+        transfer_with_payout(
+            &contract_id,
+            &bidder_id,
+            &token_id,
+            approval_id,
+            "Collection offer filled on Matt Market".to_string(),
+            U128(offer.price_per_token.0 - fee),
+        ).then(ext_self::nft_resolve_collection_offer(
+            contract_id,
+            token_id,
+            owner_id,
+            bidder_id,
+            fee.into(),
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_ROYALTIES,
+        ))
+    }
+
+    fn internal_remove_collection_offer(&mut self, contract_id: &AccountId, bidder_id: &AccountId) {
+        self.collection_offers.remove(&collection_offer_id(contract_id, bidder_id));
+        let mut bidder_ids = self.collection_offers_by_contract.get(contract_id).expect("No offers for contract");
+        bidder_ids.remove(bidder_id);
+        if bidder_ids.is_empty() {
+            self.collection_offers_by_contract.remove(contract_id);
+        } else {
+            self.collection_offers_by_contract.insert(contract_id, &bidder_ids);
+        }
+    }
+}
+
+fn collection_offer_id(contract_id: &AccountId, bidder_id: &AccountId) -> String {
+    format!("{}:{}", contract_id, bidder_id)
+}
@@ -0,0 +1,74 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// view methods
+
+    pub fn get_supply_sales(&self) -> U64 {
+        self.sales.len().into()
+    }
+
+    pub fn get_sales(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Sale> {
+        let start = u128::from(from_index.unwrap_or(U128(0))) as u64;
+        let values = self.sales.values_as_vector();
+        (start..std::cmp::min(start + limit.unwrap_or(DEFAULT_PAGE_LIMIT), values.len()))
+            .map(|index| values.get(index).unwrap().with_current_price())
+            .collect()
+    }
+
+    pub fn get_supply_by_owner_id(&self, account_id: ValidAccountId) -> U64 {
+        self.by_owner_id
+            .get(account_id.as_ref())
+            .map(|sales_set| sales_set.len())
+            .unwrap_or(0)
+            .into()
+    }
+
+    pub fn get_sales_by_owner_id(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Sale> {
+        let sales_set = if let Some(sales_set) = self.by_owner_id.get(account_id.as_ref()) {
+            sales_set
+        } else {
+            return vec![];
+        };
+        let start = u128::from(from_index.unwrap_or(U128(0))) as u64;
+        let keys = sales_set.as_vector();
+        (start..std::cmp::min(start + limit.unwrap_or(DEFAULT_PAGE_LIMIT), keys.len()))
+            .map(|index| self.sales.get(&keys.get(index).unwrap()).unwrap().with_current_price())
+            .collect()
+    }
+
+    pub fn get_supply_by_nft_contract_id(&self, nft_contract_id: ValidAccountId) -> U64 {
+        self.by_nft_contract_id
+            .get(nft_contract_id.as_ref())
+            .map(|sales_set| sales_set.len())
+            .unwrap_or(0)
+            .into()
+    }
+
+    pub fn get_sales_by_nft_contract_id(
+        &self,
+        nft_contract_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Sale> {
+        let contract_id: AccountId = nft_contract_id.into();
+        let sales_set = if let Some(sales_set) = self.by_nft_contract_id.get(&contract_id) {
+            sales_set
+        } else {
+            return vec![];
+        };
+        let start = u128::from(from_index.unwrap_or(U128(0))) as u64;
+        let keys = sales_set.as_vector();
+        (start..std::cmp::min(start + limit.unwrap_or(DEFAULT_PAGE_LIMIT), keys.len()))
+            .map(|index| {
+                let token_id = keys.get(index).unwrap();
+                self.sales.get(&format!("{}:{}", contract_id, token_id)).unwrap().with_current_price()
+            })
+            .collect()
+    }
+}
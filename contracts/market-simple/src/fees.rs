@@ -0,0 +1,88 @@
+use crate::*;
+
+pub const FEE_TOTAL_BPS: u32 = 10_000;
+/// 10%
+pub const MAX_FEE_BPS: u32 = 1_000;
+
+fn assert_valid_fee_bps(fee_bps: u32) {
+    assert!(fee_bps <= MAX_FEE_BPS, "Fee cannot be more than {} basis points", MAX_FEE_BPS);
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn set_fee_bps(&mut self, fee_bps: u32) {
+        self.assert_owner();
+        assert_valid_fee_bps(fee_bps);
+        self.fee_bps = fee_bps;
+    }
+
+    /// `None` removes the override and the contract falls back to `fee_bps`
+    pub fn set_nft_contract_fee_bps(&mut self, nft_contract_id: ValidAccountId, fee_bps: Option<u32>) {
+        self.assert_owner();
+        if let Some(fee_bps) = fee_bps {
+            assert_valid_fee_bps(fee_bps);
+            self.fee_bps_by_nft_contract_id.insert(nft_contract_id.as_ref(), &fee_bps);
+        } else {
+            self.fee_bps_by_nft_contract_id.remove(nft_contract_id.as_ref());
+        }
+    }
+
+    /// withdraws NEAR, or the fees of `ft_token_id`, to the owner
+    pub fn withdraw_treasury(&mut self, amount: Option<U128>, ft_token_id: Option<ValidAccountId>) {
+        self.assert_owner();
+        let ft_token_id: Option<AccountId> = ft_token_id.map(|ft_token_id| ft_token_id.into());
+        let treasury = self.internal_treasury(ft_token_id.as_ref());
+        let amount = amount.map(|amount| amount.0).unwrap_or(treasury);
+        assert!(amount <= treasury, "The amount is greater than the treasury balance {}", treasury);
+        if let Some(ft_token_id) = &ft_token_id {
+            self.ft_treasury.insert(ft_token_id, &(treasury - amount));
+        } else {
+            self.treasury = treasury - amount;
+        }
+        if amount > 0 {
+            pay(self.owner_id.clone(), amount, ft_token_id.as_ref());
+        }
+    }
+
+    /// view methods
+
+    pub fn get_fee_bps(&self, nft_contract_id: Option<ValidAccountId>) -> u32 {
+        nft_contract_id
+            .and_then(|nft_contract_id| self.fee_bps_by_nft_contract_id.get(nft_contract_id.as_ref()))
+            .unwrap_or(self.fee_bps)
+    }
+
+    pub fn get_treasury(&self, ft_token_id: Option<ValidAccountId>) -> U128 {
+        self.internal_treasury(ft_token_id.as_ref().map(|ft_token_id| ft_token_id.as_ref())).into()
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_owner(&self) {
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "Owner's method");
+    }
+
+    /// the marketplace's cut of `price`, taken before the token contract's payout
+    pub(crate) fn internal_fee(&self, nft_contract_id: &AccountId, price: Balance) -> Balance {
+        let fee_bps = self.fee_bps_by_nft_contract_id.get(nft_contract_id).unwrap_or(self.fee_bps);
+        price / Balance::from(FEE_TOTAL_BPS) * Balance::from(fee_bps)
+            + price % Balance::from(FEE_TOTAL_BPS) * Balance::from(fee_bps) / Balance::from(FEE_TOTAL_BPS)
+    }
+
+    pub(crate) fn internal_accrue_fee(&mut self, fee: Balance, ft_token_id: Option<&AccountId>) {
+        if let Some(ft_token_id) = ft_token_id {
+            let treasury = self.internal_treasury(Some(ft_token_id));
+            self.ft_treasury.insert(ft_token_id, &(treasury + fee));
+        } else {
+            self.treasury += fee;
+        }
+    }
+
+    fn internal_treasury(&self, ft_token_id: Option<&AccountId>) -> Balance {
+        if let Some(ft_token_id) = ft_token_id {
+            self.ft_treasury.get(ft_token_id).unwrap_or(0)
+        } else {
+            self.treasury
+        }
+    }
+}
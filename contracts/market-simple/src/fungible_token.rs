@@ -0,0 +1,129 @@
+use crate::*;
+use near_sdk::PromiseOrValue;
+
+/// msg a buyer passes to `ft_transfer_call` on a whitelisted token
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurchaseArgs {
+    pub token_contract_id: ValidAccountId,
+    pub token_id: TokenId,
+}
+
+#[ext_contract(ext_ft)]
+trait ExtFungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+/// NEP-141 receiver, buys a sale priced in the sending token
+trait FungibleTokenReceiver {
+    fn ft_on_transfer(&mut self, sender_id: ValidAccountId, amount: U128, msg: String) -> PromiseOrValue<U128>;
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for Contract {
+    /// the unused part of `amount` is returned to the buyer by the token contract
+    fn ft_on_transfer(&mut self, sender_id: ValidAccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        let ft_token_id = env::predecessor_account_id();
+        assert!(self.ft_token_ids.contains(&ft_token_id), "Token {} is not supported", ft_token_id);
+        let PurchaseArgs { token_contract_id, token_id } = near_sdk::serde_json::from_str(&msg).expect("Valid PurchaseArgs");
+        let contract_id: AccountId = token_contract_id.into();
+        let contract_and_token_id = format!("{}:{}", contract_id, token_id);
+        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        assert_eq!(sale.processing, false, "Sale is currently in progress");
+        assert!(!sale.is_expired(), "Sale has expired");
+        let buyer_id: AccountId = sender_id.into();
+        assert_ne!(buyer_id, sale.owner_id, "Cannot buy your own sale");
+        let price = *sale.ft_prices.get(&ft_token_id).expect("Sale is not priced in this token");
+        assert!(amount.0 >= price.0, "Must pay at least the sale amount {}", price.0);
+
+        sale.processing = true;
+        self.sales.insert(&contract_and_token_id, &sale);
+        self.internal_purchase(contract_id, token_id, &sale, buyer_id, price, Some(ft_token_id));
+        PromiseOrValue::Value(U128(amount.0 - price.0))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn add_ft_token_ids(&mut self, ft_token_ids: Vec<ValidAccountId>) {
+        self.assert_owner();
+        for ft_token_id in ft_token_ids {
+            self.ft_token_ids.insert(ft_token_id.as_ref());
+        }
+    }
+
+    /// existing sales keep their prices but can no longer be bought in these tokens
+    pub fn remove_ft_token_ids(&mut self, ft_token_ids: Vec<ValidAccountId>) {
+        self.assert_owner();
+        for ft_token_id in ft_token_ids {
+            self.ft_token_ids.remove(ft_token_id.as_ref());
+        }
+    }
+
+    /// pays out what failed to reach the caller, e.g. once they registered with the token contract
+    pub fn withdraw_ft_unpaid(&mut self, ft_token_id: ValidAccountId) {
+        let ft_token_id: AccountId = ft_token_id.into();
+        let account_id = env::predecessor_account_id();
+        let amount = self.ft_unpaid.remove(&ft_unpaid_id(&ft_token_id, &account_id)).expect("Nothing to withdraw");
+        pay(account_id, amount, Some(&ft_token_id));
+    }
+
+    /// self callback
+
+    /// a failed ft_transfer leaves the tokens with the market, they are held for the receiver
+    pub fn on_ft_paid(&mut self, ft_token_id: AccountId, receiver_id: AccountId, amount: U128) -> bool {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Method is private"
+        );
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+        let id = ft_unpaid_id(&ft_token_id, &receiver_id);
+        let unpaid = self.ft_unpaid.get(&id).unwrap_or(0);
+        self.ft_unpaid.insert(&id, &(unpaid + amount.0));
+        false
+    }
+
+    /// view methods
+
+    pub fn supported_ft_token_ids(&self) -> Vec<AccountId> {
+        self.ft_token_ids.to_vec()
+    }
+
+    pub fn get_ft_unpaid(&self, ft_token_id: ValidAccountId, account_id: ValidAccountId) -> U128 {
+        self.ft_unpaid.get(&ft_unpaid_id(ft_token_id.as_ref(), account_id.as_ref())).unwrap_or(0).into()
+    }
+}
+
+fn ft_unpaid_id(ft_token_id: &AccountId, account_id: &AccountId) -> String {
+    format!("{}:{}", ft_token_id, account_id)
+}
+
+/// sends NEAR, or `amount` of the fungible token when the sale was paid in one
+/// FT that can't be delivered (e.g. the receiver isn't registered) is held in `ft_unpaid` by `on_ft_paid`
+pub(crate) fn pay(receiver_id: AccountId, amount: Balance, ft_token_id: Option<&AccountId>) {
+    if amount == 0 {
+        return;
+    }
+    if let Some(ft_token_id) = ft_token_id {
+        ext_ft::ft_transfer(
+            receiver_id.clone(),
+            amount.into(),
+            None,
+            ft_token_id,
+            1,
+            GAS_FOR_FT_TRANSFER,
+        ).then(ext_self::on_ft_paid(
+            ft_token_id.clone(),
+            receiver_id,
+            amount.into(),
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_ON_FT_PAID,
+        ));
+    } else {
+        Promise::new(receiver_id).transfer(amount);
+    }
+}
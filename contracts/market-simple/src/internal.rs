@@ -0,0 +1,261 @@
+use crate::*;
+
+pub(crate) fn assert_one_yocto() {
+    assert_eq!(
+        env::attached_deposit(),
+        1,
+        "Requires attached deposit of exactly 1 yoctoNEAR"
+    );
+}
+
+pub(crate) fn unique_prefix(prefix: u8, key: &str) -> Vec<u8> {
+    let mut unique_prefix = Vec::with_capacity(33);
+    unique_prefix.push(prefix);
+    unique_prefix.extend(env::sha256(key.as_bytes()));
+    unique_prefix
+}
+
+/// nothing is listed when filling offers, storage deposits forwarded by the token contract go back to the owner
+pub(crate) fn refund_deposit(owner_id: &AccountId) {
+    let deposit = env::attached_deposit();
+    if deposit > 0 {
+        Promise::new(owner_id.clone()).transfer(deposit);
+    }
+}
+
+/// pays everyone in the token contract's payout and returns what's left of `price`
+/// the owner's share goes to `beneficiary`, whoever the sale was made on behalf of
+/// `value` is empty when `on_transfer_payout` fell back to `nft_transfer`, without a payout
+/// (or with a malformed one) the remainder is everything
+pub(crate) fn pay_out(value: &[u8], price: Balance, owner_id: &AccountId, beneficiary: &AccountId, ft_token_id: Option<&AccountId>) -> Balance {
+    let payout = parse_payout(value, price).unwrap_or_default();
+    let mut remainder = price;
+    for (receiver_id, amount) in payout {
+        let amount: Balance = amount.into();
+        remainder -= amount;
+        let receiver_id = if &receiver_id == owner_id {
+            beneficiary.clone()
+        } else {
+            receiver_id
+        };
+        if amount > 0 {
+            pay(receiver_id, amount, ft_token_id);
+        }
+    }
+    remainder
+}
+
+/// transfers the token through `nft_transfer_payout`, falling back to `nft_transfer` in `on_transfer_payout`
+/// the token contract rejects the transfer if the owner re-approved or transferred since `approval_id`
+pub(crate) fn transfer_with_payout(
+    contract_id: &AccountId,
+    receiver_id: &AccountId,
+    token_id: &TokenId,
+    approval_id: u64,
+    memo: String,
+    balance: U128,
+) -> Promise {
+    ext_transfer::nft_transfer_payout(
+        ValidAccountId::try_from(receiver_id.clone()).unwrap(),
+        token_id.clone(),
+        Some(approval_id),
+        Some(memo.clone()),
+        balance,
+        MAX_LEN_PAYOUT,
+        contract_id,
+        1,
+        GAS_FOR_NFT_TRANSFER,
+    ).then(ext_self::on_transfer_payout(
+        contract_id.clone(),
+        receiver_id.clone(),
+        token_id.clone(),
+        approval_id,
+        memo,
+        &env::current_account_id(),
+        NO_DEPOSIT,
+        GAS_FOR_ON_TRANSFER_PAYOUT,
+    ))
+}
+
+impl Contract {
+    /// transfers the token to buyer_id through the token contract, `price` is escrowed by the market
+    /// in NEAR or, with `ft_token_id`, in that fungible token
+    pub(crate) fn internal_purchase(
+        &mut self,
+        contract_id: AccountId,
+        token_id: TokenId,
+        sale: &Sale,
+        buyer_id: AccountId,
+        price: U128,
+        ft_token_id: Option<AccountId>,
+    ) -> Promise {
+        let fee = self.internal_fee(&contract_id, price.0);
+        let gas_for_royalties = if ft_token_id.is_some() { GAS_FOR_FT_ROYALTIES } else { GAS_FOR_ROYALTIES };
+        // the token contract returns how what's left after the fee should be split
+        transfer_with_payout(
+            &contract_id,
+            &buyer_id,
+            &token_id,
+            sale.approval_id,
+            "Sold by Matt Market".to_string(),
+            U128(price.0 - fee),
+        ).then(ext_self::nft_resolve_purchase(
+            contract_id,
+            token_id,
+            buyer_id,
+            price,
+            fee.into(),
+            ft_token_id,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            gas_for_royalties,
+        ))
+    }
+
+    /// lists the token for the owner of the approval, only reached through `nft_on_approve`
+    /// so the token contract vouches for `owner_id` and `approval_id`
+    /// the attached deposit (what nft_approve forwarded) is added to the seller's storage balance
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn internal_add_sale(&mut self, token_contract_id: ValidAccountId, token_id: String, price: U128, owner_id: ValidAccountId, approval_id: u64, beneficiary: Option<ValidAccountId>, auction: Option<AuctionArgs>, dutch_auction: Option<DutchAuctionArgs>, ft_prices: Option<HashMap<AccountId, U128>>, expires_at: Option<U64>) {
+        let contract_id: AccountId = token_contract_id.into();
+        // storage_per_sale is measured for the largest sale these allow
+        assert!(token_id.len() <= MAX_TOKEN_ID_LENGTH, "Token ID longer than {}", MAX_TOKEN_ID_LENGTH);
+
+        // if you are making a sale on someone's behalf and you want to escrow the funds (guest accounts)
+        let mut sale_beneficiary = owner_id.clone();
+        if let Some(beneficiary) = beneficiary {
+            sale_beneficiary = beneficiary;
+        }
+
+        assert!(auction.is_none() || dutch_auction.is_none(), "A sale can only be one kind of auction");
+        let ft_prices = ft_prices.unwrap_or_default();
+        assert!(ft_prices.len() <= MAX_FT_PRICES, "Sales can be priced in at most {} tokens", MAX_FT_PRICES);
+        assert!(ft_prices.is_empty() || (auction.is_none() && dutch_auction.is_none()), "Auctions are priced in NEAR only");
+        for ft_token_id in ft_prices.keys() {
+            assert!(self.ft_token_ids.contains(ft_token_id), "Token {} is not supported", ft_token_id);
+        }
+        if let Some(expires_at) = expires_at {
+            assert!(auction.is_none(), "Auctions end at their end_at");
+            assert!(expires_at.0 > env::block_timestamp(), "Sale must expire in the future");
+        }
+
+        let owner_id: AccountId = owner_id.into();
+        let contract_and_token_id = format!("{}:{}", contract_id, token_id);
+        if let Some(sale) = self.sales.get(&contract_and_token_id) {
+            assert!(!sale.processing, "Sale is currently in progress");
+            assert!(
+                sale.owner_id == owner_id,
+                "Token is listed by {}, that listing has to be removed or pruned first",
+                sale.owner_id
+            );
+            assert!(!sale.has_bids(), "Cannot relist an auction with bids");
+        }
+        let sale = Sale {
+            nft_contract_id: contract_id.clone(),
+            token_id: token_id.clone(),
+            owner_id: owner_id.clone(),
+            approval_id,
+            beneficiary: sale_beneficiary.into(),
+            price,
+            processing: false,
+            auction: auction.map(Auction::from),
+            dutch_auction: dutch_auction.map(|args| DutchAuction::new(args, price)),
+            ft_prices,
+            expires_at,
+        };
+        let previous_sale = self.sales.insert(&contract_and_token_id, &sale);
+        if let Some(previous_sale) = &previous_sale {
+            self.internal_unindex_sale(previous_sale);
+        }
+        self.internal_index_sale(&sale);
+        let deposit = env::attached_deposit();
+        if deposit > 0 {
+            self.internal_deposit_storage(&owner_id, deposit);
+        }
+        self.internal_assert_storage(&owner_id);
+
+        let sale_log = vec![SaleLog {
+            nft_contract_id: contract_id,
+            token_id,
+            owner_id,
+            price,
+        }];
+        if previous_sale.is_some() {
+            MarketEventKind::SaleUpdated(sale_log).emit();
+        } else {
+            MarketEventKind::SaleCreated(sale_log).emit();
+        }
+    }
+
+    /// removes the sale, its storage becomes available to the seller again
+    pub(crate) fn internal_remove_sale(&mut self, contract_id: AccountId, token_id: TokenId) -> Sale {
+        let sale = self.internal_take_sale(&contract_id, &token_id);
+
+        MarketEventKind::SaleRemoved(vec![SaleLog {
+            nft_contract_id: contract_id,
+            token_id,
+            owner_id: sale.owner_id.clone(),
+            price: sale.price,
+        }])
+        .emit();
+        sale
+    }
+
+    /// the token changed hands outside its listing (e.g. an accepted offer), so the listing is stale
+    /// an auction's highest bid is refunded, a sale mid-purchase is left to `nft_resolve_purchase`
+    pub(crate) fn internal_remove_stale_sale(&mut self, contract_id: &AccountId, token_id: &TokenId) {
+        let sale = match self.sales.get(&format!("{}:{}", contract_id, token_id)) {
+            Some(sale) => sale,
+            None => return,
+        };
+        if sale.processing {
+            return;
+        }
+        if let Some(Auction { bidder_id: Some(bidder_id), bid, .. }) = &sale.auction {
+            Promise::new(bidder_id.clone()).transfer(bid.0);
+        }
+        self.internal_remove_sale(contract_id.clone(), token_id.clone());
+    }
+
+    /// removes the sale from `sales` and both indexes
+    pub(crate) fn internal_take_sale(&mut self, contract_id: &AccountId, token_id: &TokenId) -> Sale {
+        let sale = self.sales.remove(&format!("{}:{}", contract_id, token_id)).expect("No sale");
+        self.internal_unindex_sale(&sale);
+        sale
+    }
+
+    pub(crate) fn internal_index_sale(&mut self, sale: &Sale) {
+        let contract_and_token_id = format!("{}:{}", sale.nft_contract_id, sale.token_id);
+        let mut by_owner_id = self.by_owner_id.get(&sale.owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(unique_prefix(b'R', &sale.owner_id))
+        });
+        by_owner_id.insert(&contract_and_token_id);
+        self.by_owner_id.insert(&sale.owner_id, &by_owner_id);
+
+        let mut by_nft_contract_id = self.by_nft_contract_id.get(&sale.nft_contract_id).unwrap_or_else(|| {
+            UnorderedSet::new(unique_prefix(b'N', &sale.nft_contract_id))
+        });
+        by_nft_contract_id.insert(&sale.token_id);
+        self.by_nft_contract_id.insert(&sale.nft_contract_id, &by_nft_contract_id);
+    }
+
+    pub(crate) fn internal_unindex_sale(&mut self, sale: &Sale) {
+        let contract_and_token_id = format!("{}:{}", sale.nft_contract_id, sale.token_id);
+        if let Some(mut by_owner_id) = self.by_owner_id.get(&sale.owner_id) {
+            by_owner_id.remove(&contract_and_token_id);
+            if by_owner_id.is_empty() {
+                self.by_owner_id.remove(&sale.owner_id);
+            } else {
+                self.by_owner_id.insert(&sale.owner_id, &by_owner_id);
+            }
+        }
+        if let Some(mut by_nft_contract_id) = self.by_nft_contract_id.get(&sale.nft_contract_id) {
+            by_nft_contract_id.remove(&sale.token_id);
+            if by_nft_contract_id.is_empty() {
+                self.by_nft_contract_id.remove(&sale.nft_contract_id);
+            } else {
+                self.by_nft_contract_id.insert(&sale.nft_contract_id, &by_nft_contract_id);
+            }
+        }
+    }
+}
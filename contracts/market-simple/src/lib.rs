@@ -1,96 +1,210 @@
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap};
-use near_sdk::json_types::{U128, ValidAccountId};
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::json_types::{U128, U64, ValidAccountId};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, ext_contract, near_bindgen, AccountId, Gas, Balance, PanicOnDefault, Promise, PromiseResult};
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, Gas, Balance, PanicOnDefault, Promise, PromiseOrValue, PromiseResult, StorageUsage};
+use nft_events::{AuctionBidLog, CollectionOfferLog, MarketEventKind, OfferLog, SaleCompletedLog, SaleLog};
+
+use crate::internal::*;
+pub use crate::auction::*;
+pub use crate::collection_offers::*;
+pub use crate::enumeration::*;
+pub use crate::fees::*;
+pub use crate::fungible_token::*;
+pub use crate::offers::*;
+pub use crate::prune::*;
+pub use crate::storage::*;
+
+mod auction;
+mod collection_offers;
+mod enumeration;
+mod fees;
+mod fungible_token;
+mod internal;
+mod offers;
+mod prune;
+mod storage;
 
 #[global_allocator]
 static ALLOC: near_sdk::wee_alloc::WeeAlloc<'_> = near_sdk::wee_alloc::WeeAlloc::INIT;
 
-const GAS_FOR_RESOLVE_TRANSFER: Gas = 10_000_000_000_000;
-const GAS_FOR_NFT_TRANSFER_CALL: Gas = 25_000_000_000_000 + GAS_FOR_RESOLVE_TRANSFER;
+const GAS_FOR_NFT_TRANSFER: Gas = 15_000_000_000_000;
+/// retrying a transfer without a payout needs another nft_transfer
+const GAS_FOR_ON_TRANSFER_PAYOUT: Gas = 20_000_000_000_000;
+/// resolving a purchase creates one transfer per payout receiver
+const GAS_FOR_ROYALTIES: Gas = 115_000_000_000_000;
+/// FT payouts also resolve every ft_transfer, buyers paying in FT attach more gas to ft_transfer_call
+const GAS_FOR_FT_ROYALTIES: Gas = 180_000_000_000_000;
+const GAS_FOR_FT_TRANSFER: Gas = 5_000_000_000_000;
+const GAS_FOR_ON_FT_PAID: Gas = 5_000_000_000_000;
 const NO_DEPOSIT: Balance = 0;
-const MIN_ATTACHED_DEPOSIT: u128 = 100_000_000_000_000_000_000_000;
+/// the escrow stays on the market, so it also covers the storage of the offer
+const MIN_OFFER_AMOUNT: u128 = 100_000_000_000_000_000_000_000;
+/// royalty receivers + the owner, the token contract panics if it needs more
+const MAX_LEN_PAYOUT: u32 = 12;
+/// 10 minutes
+const DEFAULT_AUCTION_EXTENSION: u64 = 600_000_000_000;
+const DEFAULT_PAGE_LIMIT: u64 = 50;
+const MAX_TOKEN_ID_LENGTH: usize = 256;
+const MAX_FT_PRICES: usize = 4;
 pub type TokenId = String;
 pub type ContractAndTokenId = String;
 
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Payout {
+    pub payout: HashMap<AccountId, U128>,
+}
+
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Sale {
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
     pub owner_id: AccountId,
     pub approval_id: u64,
     pub beneficiary: AccountId,
     pub price: U128,
-    pub deposit: Balance,
     pub processing: bool,
+    pub auction: Option<Auction>,
+    pub dutch_auction: Option<DutchAuction>,
+    /// prices in whitelisted NEP-141 tokens, bought through ft_transfer_call
+    pub ft_prices: HashMap<AccountId, U128>,
+    /// anyone can prune the sale after this
+    pub expires_at: Option<U64>,
+}
+
+impl Sale {
+    pub fn has_bids(&self) -> bool {
+        self.auction.as_ref().map(|auction| auction.bidder_id.is_some()).unwrap_or(false)
+    }
+
+    /// what a buyer pays right now, only dutch auctions change over time
+    pub fn current_price(&self) -> Balance {
+        match &self.dutch_auction {
+            Some(dutch_auction) => dutch_auction.current_price(),
+            None => self.price.0,
+        }
+    }
+
+    /// views report `price` as what a buyer pays right now
+    pub fn with_current_price(mut self) -> Self {
+        self.price = self.current_price().into();
+        self
+    }
 }
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
     pub owner_id: AccountId,
-    pub sales: LookupMap<ContractAndTokenId, Sale>,
+    pub sales: UnorderedMap<ContractAndTokenId, Sale>,
+    pub by_owner_id: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+    pub by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    /// "contract:token:bidder" -> offer
+    pub offers: LookupMap<String, Offer>,
+    pub offers_by_token: LookupMap<ContractAndTokenId, UnorderedSet<AccountId>>,
+    pub offers_by_bidder: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+    /// "contract:bidder" -> collection offer
+    pub collection_offers: LookupMap<String, CollectionOffer>,
+    pub collection_offers_by_contract: LookupMap<AccountId, UnorderedSet<AccountId>>,
+    /// NEP-141 tokens sales can be priced in
+    pub ft_token_ids: UnorderedSet<AccountId>,
+    /// marketplace fee in basis points, nft contracts can be given their own
+    pub fee_bps: u32,
+    pub fee_bps_by_nft_contract_id: LookupMap<AccountId, u32>,
+    /// accrued fees, withdrawn by the owner
+    pub treasury: Balance,
+    pub ft_treasury: LookupMap<AccountId, Balance>,
+    /// "ft_token:account" -> FT that failed to transfer, withdrawn by the account
+    pub ft_unpaid: LookupMap<String, Balance>,
+    /// NEP-145 balances of sellers
+    pub storage_deposits: LookupMap<AccountId, Balance>,
+    pub storage_per_sale: StorageUsage,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
-    pub fn new(owner_id: ValidAccountId) -> Self {
+    pub fn new(owner_id: ValidAccountId, fee_bps: Option<u32>) -> Self {
         assert!(!env::state_exists(), "Already initialized");
-        Self {
+        let fee_bps = fee_bps.unwrap_or(0);
+        assert!(fee_bps <= MAX_FEE_BPS, "Fee cannot be more than {} basis points", MAX_FEE_BPS);
+        let mut this = Self {
             owner_id: owner_id.into(),
-            sales: LookupMap::new(b"s".to_vec()),
-        }
+            sales: UnorderedMap::new(b"s".to_vec()),
+            by_owner_id: LookupMap::new(b"r".to_vec()),
+            by_nft_contract_id: LookupMap::new(b"n".to_vec()),
+            offers: LookupMap::new(b"o".to_vec()),
+            offers_by_token: LookupMap::new(b"t".to_vec()),
+            offers_by_bidder: LookupMap::new(b"b".to_vec()),
+            collection_offers: LookupMap::new(b"c".to_vec()),
+            collection_offers_by_contract: LookupMap::new(b"d".to_vec()),
+            ft_token_ids: UnorderedSet::new(b"f".to_vec()),
+            fee_bps,
+            fee_bps_by_nft_contract_id: LookupMap::new(b"e".to_vec()),
+            treasury: 0,
+            ft_treasury: LookupMap::new(b"x".to_vec()),
+            ft_unpaid: LookupMap::new(b"u".to_vec()),
+            storage_deposits: LookupMap::new(b"p".to_vec()),
+            storage_per_sale: 0,
+        };
+        this.measure_storage_per_sale();
+        this
     }
 
-    #[payable]
-    pub fn add_sale(&mut self, token_contract_id: ValidAccountId, token_id: String, price: U128, owner_id: ValidAccountId, approval_id: u64, beneficiary: Option<ValidAccountId>) {
-        let deposit = env::attached_deposit();
-        assert!(deposit >= MIN_ATTACHED_DEPOSIT, "Must attach at least 0.1 NEAR as deposit to list sale");
-        let contract_id: AccountId = token_contract_id.into();
-        
-        // if you are making a sale on someone's behalf and you want to escrow the funds (guest accounts)
-        let mut sale_beneficiary = owner_id.clone();
-        if let Some(beneficiary) = beneficiary {
-            sale_beneficiary = beneficiary;
-        }
-
-        env::log(format!("add_sale for owner: {}", owner_id.clone().as_ref()).as_bytes());
-        
-        self.sales.insert(&format!("{}:{}", contract_id, token_id), &Sale{
-            owner_id: owner_id.into(),
-            approval_id,
-            beneficiary: sale_beneficiary.into(),
-            price,
-            deposit,
-            processing: false,
-        });
-    }
-    
     pub fn update_price(&mut self, token_contract_id: ValidAccountId, token_id: String, price: U128) {
         let contract_id: AccountId = token_contract_id.into();
         let contract_and_token_id = format!("{}:{}", contract_id, token_id);
-        let mut sale = self.sales.remove(&contract_and_token_id).expect("No sale");
+        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
         assert_eq!(
             env::predecessor_account_id(),
             sale.owner_id,
             "Must be sale owner"
         );
+        assert!(!sale.has_bids(), "Cannot change the reserve price of an auction with bids");
+        assert!(sale.dutch_auction.is_none(), "Cannot change the price of a dutch auction");
         sale.price = price;
         self.sales.insert(&contract_and_token_id, &sale);
+
+        MarketEventKind::SaleUpdated(vec![SaleLog {
+            nft_contract_id: contract_id,
+            token_id,
+            owner_id: sale.owner_id,
+            price,
+        }])
+        .emit();
     }
 
     /// should be able to pull a sale without yocto redirect to wallet?
     pub fn remove_sale(&mut self, token_contract_id: ValidAccountId, token_id: String) {
         let contract_id: AccountId = token_contract_id.into();
-        let sale = self.sales.remove(&format!("{}:{}", contract_id, token_id)).expect("No sale");
+        let sale = self.sales.get(&format!("{}:{}", contract_id, token_id)).expect("No sale");
         assert_eq!(
             env::predecessor_account_id(),
             sale.owner_id,
             "Must be sale owner"
         );
-        Promise::new(sale.owner_id).transfer(sale.deposit);
+        assert!(!sale.processing, "Sale is currently in progress");
+        assert!(!sale.has_bids(), "Cannot remove an auction with bids");
+        self.internal_remove_sale(contract_id, token_id);
+    }
+
+    /// lets a token contract delist for owners that can't call the market themselves (guests)
+    /// the storage balance the listing freed is sent back to the token contract
+    pub fn remove_sale_on_behalf(&mut self, token_id: String) -> U128 {
+        let contract_id = env::predecessor_account_id();
+        let sale = self.sales.get(&format!("{}:{}", contract_id, token_id)).expect("No sale");
+        assert!(!sale.processing, "Sale is currently in progress");
+        assert!(!sale.has_bids(), "Cannot remove an auction with bids");
+        self.internal_remove_sale(contract_id.clone(), token_id);
+        let refund = self.internal_withdraw_storage(&sale.owner_id, self.storage_per_sale_cost());
+        if refund > 0 {
+            Promise::new(contract_id).transfer(refund);
+        }
+        refund.into()
     }
 
     #[payable]
@@ -99,78 +213,199 @@ impl Contract {
         let contract_and_token_id = format!("{}:{}", contract_id, token_id);
         let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
         assert_eq!(sale.processing, false, "Sale is currently in progress");
+        assert!(!sale.is_expired(), "Sale has expired");
+        assert!(sale.auction.is_none(), "Auctions are sold through bid and settle_auction");
         let deposit = env::attached_deposit();
-        let price = sale.price.into();
-        assert_eq!(
-            env::attached_deposit(),
-            price,
-            "Must pay exactly the sale amount {}", deposit
-        );
+        let buyer_id = env::predecessor_account_id();
+        let price = if let Some(dutch_auction) = &sale.dutch_auction {
+            assert!(env::block_timestamp() >= dutch_auction.start_at.0, "Auction has not started");
+            let price = sale.current_price();
+            assert!(deposit >= price, "Must pay at least the current price {}", price);
+            // the price may have dropped since the buyer signed
+            if deposit > price {
+                Promise::new(buyer_id.clone()).transfer(deposit - price);
+            }
+            price
+        } else {
+            let price = sale.price.into();
+            assert_eq!(
+                deposit,
+                price,
+                "Must pay exactly the sale amount {}", deposit
+            );
+            price
+        };
         sale.processing = true;
         self.sales.insert(&contract_and_token_id, &sale);
-        let predecessor = env::predecessor_account_id();
-        let receiver_id = ValidAccountId::try_from(predecessor.clone()).unwrap();
-        let owner_id = ValidAccountId::try_from(sale.owner_id).unwrap();
-        let memo: String = "Sold by Matt Market".to_string();
-        // call NFT contract transfer call function
+        self.internal_purchase(contract_id, token_id, &sale, buyer_id, price.into(), None)
+    }
+
+    /// self callbacks
+
+    /// passes the token contract's payout on to the resolver
+    /// token contracts without `nft_transfer_payout` get a plain `nft_transfer` instead, the resolver
+    /// then sees no payout and the seller gets the whole price
+    pub fn on_transfer_payout(
+        &mut self,
+        token_contract_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: u64,
+        memo: String,
+    ) -> PromiseOrValue<Payout> {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Method is private"
+        );
+        if let PromiseResult::Successful(value) = env::promise_result(0) {
+            let payout = near_sdk::serde_json::from_slice::<Payout>(&value).unwrap_or(Payout { payout: HashMap::new() });
+            return PromiseOrValue::Value(payout);
+        }
+        // a stale approval fails this transfer too and the resolver refunds the buyer
         ext_transfer::nft_transfer(
-            receiver_id,
-            token_id.clone(),
-            owner_id, // who added sale must still be token owner
-            memo,
-            &contract_id,
-            1,
-            env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL,
-        ).then(ext_self::nft_resolve_purchase(
-            contract_id,
+            ValidAccountId::try_from(receiver_id).unwrap(),
             token_id,
-            predecessor,
-            &env::current_account_id(),
-            NO_DEPOSIT,
-            GAS_FOR_RESOLVE_TRANSFER,
-        ))
+            Some(approval_id),
+            Some(memo),
+            &token_contract_id,
+            1,
+            GAS_FOR_NFT_TRANSFER,
+        ).into()
     }
 
-    /// self callback
-
     pub fn nft_resolve_purchase(
         &mut self,
         token_contract_id: AccountId,
         token_id: TokenId,
         buyer_id: AccountId,
+        price: U128,
+        fee: U128,
+        ft_token_id: Option<AccountId>,
     ) -> bool {
-        env::log(format!("Promise Result {:?}", env::promise_result(0)).as_bytes());
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Method is private"
+        );
         let contract_and_token_id = format!("{}:{}", token_contract_id, token_id);
-        // value is nothing, checking if nft_transfer was Successful promise execution
-        if let PromiseResult::Successful(_value) = env::promise_result(0) {
-            // pay seller and remove sale
-            let sale = self.sales.remove(&contract_and_token_id).expect("No sale");
-            Promise::new(sale.beneficiary).transfer(u128::from(sale.price) + sale.deposit);
+        // checking if nft_transfer_payout was Successful promise execution
+        if let PromiseResult::Successful(value) = env::promise_result(0) {
+            // pay everyone in the payout and remove sale
+            let sale = self.internal_take_sale(&token_contract_id, &token_id);
+            // the token contract only split what was left after the marketplace fee
+            self.internal_accrue_fee(fee.0, ft_token_id.as_ref());
+            let remainder = pay_out(&value, price.0 - fee.0, &sale.owner_id, &sale.beneficiary, ft_token_id.as_ref());
+            pay(sale.beneficiary, remainder, ft_token_id.as_ref());
+
+            MarketEventKind::SaleCompleted(vec![SaleCompletedLog {
+                nft_contract_id: token_contract_id,
+                token_id,
+                owner_id: sale.owner_id,
+                buyer_id,
+                price: price.into(),
+                ft_token_id,
+                fee: Some(fee),
+            }])
+            .emit();
             return true;
         }
-        // no promise result, refund buyer and update sale state to not processing
-        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
-        sale.processing = false;
-        self.sales.insert(&contract_and_token_id, &sale);
-        Promise::new(buyer_id).transfer(u128::from(sale.price));
+        // transfer was rejected (e.g. stale approval_id), refund buyer
+        pay(buyer_id, price.into(), ft_token_id.as_ref());
+        // the sale may already be gone, the refund above must not be rolled back
+        if let Some(mut sale) = self.sales.get(&contract_and_token_id) {
+            if sale.auction.is_some() {
+                // the auction is over and the token can't be delivered
+                self.internal_remove_sale(token_contract_id, token_id);
+            } else {
+                // update sale state to not processing
+                sale.processing = false;
+                self.sales.insert(&contract_and_token_id, &sale);
+            }
+        }
         return false;
     }
 
     /// view methods
 
+    /// `price` is the live price, dutch auctions keep their listed price in `dutch_auction.start_price`
     pub fn get_sale(&self, token_contract_id: ValidAccountId, token_id: String) -> Sale {
         let contract_id: AccountId = token_contract_id.into();
-        self.sales.get(&format!("{}:{}", contract_id, token_id.clone())).expect("No sale")
+        self.sales.get(&format!("{}:{}", contract_id, token_id.clone())).expect("No sale").with_current_price()
+    }
+}
+
+/// payout is only valid if it fits in the price that was passed to the token contract
+fn parse_payout(value: &[u8], price: Balance) -> Option<HashMap<AccountId, U128>> {
+    let Payout { payout } = near_sdk::serde_json::from_slice::<Payout>(value).ok()?;
+    if payout.len() as u32 > MAX_LEN_PAYOUT {
+        return None;
+    }
+    let mut total: Balance = 0;
+    for (receiver_id, amount) in payout.iter() {
+        if !env::is_valid_account_id(receiver_id.as_bytes()) {
+            return None;
+        }
+        total = total.checked_add(amount.0)?;
+    }
+    if total > price {
+        return None;
     }
+    Some(payout)
 }
 
 #[ext_contract(ext_self)]
 trait ResolvePurchase {
+    fn on_transfer_payout(
+        &mut self,
+        token_contract_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: u64,
+        memo: String,
+    ) -> Promise;
+
     fn nft_resolve_purchase(
         &mut self,
         token_contract_id: AccountId,
         token_id: TokenId,
         buyer_id: AccountId,
+        price: U128,
+        fee: U128,
+        ft_token_id: Option<AccountId>,
+    ) -> Promise;
+
+    fn on_ft_paid(
+        &mut self,
+        ft_token_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> Promise;
+
+    fn on_prune_check(
+        &mut self,
+        token_contract_id: AccountId,
+        token_id: TokenId,
+        approval_id: u64,
+        pruner_id: AccountId,
+    ) -> Promise;
+
+    fn nft_resolve_offer(
+        &mut self,
+        token_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        bidder_id: AccountId,
+        fee: U128,
+    ) -> Promise;
+
+    fn nft_resolve_collection_offer(
+        &mut self,
+        token_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        bidder_id: AccountId,
+        fee: U128,
     ) -> Promise;
 }
 
@@ -180,9 +415,26 @@ trait ExtTransfer {
         &mut self,
         receiver_id: ValidAccountId,
         token_id: TokenId,
-        enforce_owner_id: ValidAccountId,
-        memo: String,
+        enforce_approval_id: Option<u64>,
+        memo: Option<String>,
     );
+
+    fn nft_transfer_payout(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        balance: U128,
+        max_len_payout: u32,
+    ) -> Payout;
+
+    fn nft_is_approved(
+        &self,
+        token_id: TokenId,
+        approved_account_id: ValidAccountId,
+        approval_id: Option<u64>,
+    ) -> bool;
 }
 
 /// approval callbacks from NFT contracts 
@@ -203,6 +455,10 @@ trait NonFungibleTokenApprovalsReceiver {
 pub struct OnApprovalMsg {
     pub beneficiary: AccountId,
     pub price: U128,
+    pub auction: Option<AuctionArgs>,
+    pub dutch_auction: Option<DutchAuctionArgs>,
+    pub ft_prices: Option<HashMap<AccountId, U128>>,
+    pub expires_at: Option<U64>,
 }
 
 #[near_bindgen]
@@ -220,9 +476,19 @@ impl NonFungibleTokenApprovalsReceiver for Contract {
         let contract: AccountId = token_contract_id.clone().into();
         assert_eq!(env::predecessor_account_id(), contract, "Approval callbacks need to be called by the NFT Contract");
         if let Some(msg) = msg {
+            if let Ok(AcceptOfferMsg { accept_offer }) = near_sdk::serde_json::from_str(&msg) {
+                refund_deposit(owner_id.as_ref());
+                self.internal_accept_offer(contract, token_id, owner_id.into(), approval_id, accept_offer.bidder_id);
+                return true;
+            }
+            if let Ok(FillCollectionOfferMsg { fill_collection_offer }) = near_sdk::serde_json::from_str(&msg) {
+                refund_deposit(owner_id.as_ref());
+                self.internal_fill_collection_offer(contract, token_id, owner_id.into(), approval_id, fill_collection_offer.bidder_id);
+                return true;
+            }
             let msg_data: OnApprovalMsg = near_sdk::serde_json::from_str(&msg).expect("Valid OnApprovalMsg");
             let beneficiary = ValidAccountId::try_from(msg_data.beneficiary).expect("Valid account id passd in msg to nft_on_approve_account_id");
-            self.add_sale(token_contract_id, token_id, msg_data.price.into(), owner_id, approval_id, Some(beneficiary));
+            self.internal_add_sale(token_contract_id, token_id, msg_data.price.into(), owner_id, approval_id, Some(beneficiary), msg_data.auction, msg_data.dutch_auction, msg_data.ft_prices, msg_data.expires_at);
             true
         } else {
             false
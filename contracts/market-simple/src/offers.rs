@@ -0,0 +1,256 @@
+use crate::*;
+
+/// escrowed NEAR offered for a token that may or may not be listed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Offer {
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+    pub bidder_id: AccountId,
+    pub amount: U128,
+    pub expires_at: Option<U64>,
+    pub processing: bool,
+}
+
+impl Offer {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map(|expires_at| env::block_timestamp() >= expires_at.0).unwrap_or(false)
+    }
+
+    fn log(&self) -> OfferLog {
+        OfferLog {
+            nft_contract_id: self.nft_contract_id.clone(),
+            token_id: self.token_id.clone(),
+            bidder_id: self.bidder_id.clone(),
+            amount: self.amount,
+            expires_at: self.expires_at,
+        }
+    }
+}
+
+/// msg an owner passes to `nft_approve` to sell to an existing offer
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptOfferMsg {
+    pub accept_offer: AcceptOffer,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptOffer {
+    pub bidder_id: AccountId,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// the attached deposit is escrowed until the offer is accepted or cancelled
+    /// making a new offer on the same token replaces (and refunds) the previous one
+    #[payable]
+    pub fn make_offer(&mut self, token_contract_id: ValidAccountId, token_id: String, expires_at: Option<U64>) {
+        let amount = env::attached_deposit();
+        assert!(amount >= MIN_OFFER_AMOUNT, "Offers must be at least 0.1 NEAR");
+        assert!(token_id.len() <= MAX_TOKEN_ID_LENGTH, "Token ID longer than {}", MAX_TOKEN_ID_LENGTH);
+        if let Some(expires_at) = expires_at {
+            assert!(expires_at.0 > env::block_timestamp(), "Offer must expire in the future");
+        }
+        let contract_id: AccountId = token_contract_id.into();
+        let bidder_id = env::predecessor_account_id();
+        if let Some(previous_offer) = self.offers.get(&offer_id(&contract_id, &token_id, &bidder_id)) {
+            assert!(!previous_offer.processing, "Offer is currently in progress");
+            Promise::new(bidder_id.clone()).transfer(previous_offer.amount.0);
+        }
+
+        let offer = Offer {
+            nft_contract_id: contract_id,
+            token_id,
+            bidder_id,
+            amount: amount.into(),
+            expires_at,
+            processing: false,
+        };
+        self.internal_add_offer(&offer);
+        MarketEventKind::OfferCreated(vec![offer.log()]).emit();
+    }
+
+    pub fn cancel_offer(&mut self, token_contract_id: ValidAccountId, token_id: String) {
+        let contract_id: AccountId = token_contract_id.into();
+        let bidder_id = env::predecessor_account_id();
+        let offer = self.offers.get(&offer_id(&contract_id, &token_id, &bidder_id)).expect("No offer");
+        assert!(!offer.processing, "Offer is currently in progress");
+        self.internal_remove_offer(&contract_id, &token_id, &bidder_id);
+        Promise::new(bidder_id).transfer(offer.amount.0);
+        MarketEventKind::OfferRemoved(vec![offer.log()]).emit();
+    }
+
+    /// self callback
+
+    pub fn nft_resolve_offer(
+        &mut self,
+        token_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        bidder_id: AccountId,
+        fee: U128,
+    ) -> bool {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Method is private"
+        );
+        if let PromiseResult::Successful(value) = env::promise_result(0) {
+            let offer = self.internal_remove_offer(&token_contract_id, &token_id, &bidder_id);
+            self.internal_settle_offer(&token_contract_id, &token_id, &owner_id, &value, offer.amount.0, fee.0);
+
+            MarketEventKind::OfferAccepted(vec![SaleCompletedLog {
+                nft_contract_id: token_contract_id,
+                token_id,
+                owner_id,
+                buyer_id: bidder_id,
+                price: offer.amount,
+                ft_token_id: None,
+                fee: Some(fee),
+            }])
+            .emit();
+            return true;
+        }
+        // transfer was rejected, the offer stands
+        let id = offer_id(&token_contract_id, &token_id, &bidder_id);
+        let mut offer = self.offers.get(&id).expect("No offer");
+        offer.processing = false;
+        self.offers.insert(&id, &offer);
+        false
+    }
+
+    /// view methods
+
+    pub fn get_offer(&self, token_contract_id: ValidAccountId, token_id: String, bidder_id: ValidAccountId) -> Option<Offer> {
+        self.offers.get(&offer_id(token_contract_id.as_ref(), &token_id, bidder_id.as_ref()))
+    }
+
+    pub fn get_offers_by_token(&self, token_contract_id: ValidAccountId, token_id: String, from_index: Option<U128>, limit: Option<u64>) -> Vec<Offer> {
+        let contract_id: AccountId = token_contract_id.into();
+        let bidder_ids = if let Some(bidder_ids) = self.offers_by_token.get(&format!("{}:{}", contract_id, token_id)) {
+            bidder_ids
+        } else {
+            return vec![];
+        };
+        let bidder_ids = bidder_ids.as_vector();
+        let start = u128::from(from_index.unwrap_or(U128(0))) as u64;
+        (start..std::cmp::min(start + limit.unwrap_or(DEFAULT_PAGE_LIMIT), bidder_ids.len()))
+            .filter_map(|index| self.offers.get(&offer_id(&contract_id, &token_id, &bidder_ids.get(index).unwrap())))
+            .collect()
+    }
+
+    pub fn get_offers_by_bidder(&self, bidder_id: ValidAccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<Offer> {
+        let bidder_id: AccountId = bidder_id.into();
+        let contract_and_token_ids = if let Some(contract_and_token_ids) = self.offers_by_bidder.get(&bidder_id) {
+            contract_and_token_ids
+        } else {
+            return vec![];
+        };
+        let contract_and_token_ids = contract_and_token_ids.as_vector();
+        let start = u128::from(from_index.unwrap_or(U128(0))) as u64;
+        (start..std::cmp::min(start + limit.unwrap_or(DEFAULT_PAGE_LIMIT), contract_and_token_ids.len()))
+            .filter_map(|index| self.offers.get(&format!("{}:{}", contract_and_token_ids.get(index).unwrap(), bidder_id)))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// the bidder got the token, shared by both offer resolvers
+    /// keeps the marketplace fee, pays the rest of the escrowed `price` out to the token's payout and delists the token
+    pub(crate) fn internal_settle_offer(
+        &mut self,
+        contract_id: &AccountId,
+        token_id: &TokenId,
+        owner_id: &AccountId,
+        value: &[u8],
+        price: Balance,
+        fee: Balance,
+    ) {
+        self.internal_accrue_fee(fee, None);
+        let remainder = pay_out(value, price - fee, owner_id, owner_id, None);
+        Promise::new(owner_id.clone()).transfer(remainder);
+        self.internal_remove_stale_sale(contract_id, token_id);
+    }
+
+    /// called from nft_on_approve, the new approval_id lets the market transfer the token to the bidder
+    pub(crate) fn internal_accept_offer(
+        &mut self,
+        contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        bidder_id: AccountId,
+    ) -> Promise {
+        let id = offer_id(&contract_id, &token_id, &bidder_id);
+        let mut offer = self.offers.get(&id).expect("No offer");
+        assert!(!offer.processing, "Offer is currently in progress");
+        assert!(!offer.is_expired(), "Offer has expired");
+        assert_ne!(owner_id, bidder_id, "Cannot accept your own offer");
+        offer.processing = true;
+        self.offers.insert(&id, &offer);
+
+        let fee = self.internal_fee(&contract_id, offer.amount.0);
+        transfer_with_payout(
+            &contract_id,
+            &bidder_id,
+            &token_id,
+            approval_id,
+            "Offer accepted on Matt Market".to_string(),
+            U128(offer.amount.0 - fee),
+        ).then(ext_self::nft_resolve_offer(
+            contract_id,
+            token_id,
+            owner_id,
+            bidder_id,
+            fee.into(),
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_ROYALTIES,
+        ))
+    }
+
+    fn internal_add_offer(&mut self, offer: &Offer) {
+        let contract_and_token_id = format!("{}:{}", offer.nft_contract_id, offer.token_id);
+        self.offers.insert(&offer_id(&offer.nft_contract_id, &offer.token_id, &offer.bidder_id), offer);
+
+        let mut bidder_ids = self.offers_by_token.get(&contract_and_token_id).unwrap_or_else(|| {
+            UnorderedSet::new(unique_prefix(b'T', &contract_and_token_id))
+        });
+        bidder_ids.insert(&offer.bidder_id);
+        self.offers_by_token.insert(&contract_and_token_id, &bidder_ids);
+
+        let mut contract_and_token_ids = self.offers_by_bidder.get(&offer.bidder_id).unwrap_or_else(|| {
+            UnorderedSet::new(unique_prefix(b'B', &offer.bidder_id))
+        });
+        contract_and_token_ids.insert(&contract_and_token_id);
+        self.offers_by_bidder.insert(&offer.bidder_id, &contract_and_token_ids);
+    }
+
+    fn internal_remove_offer(&mut self, contract_id: &AccountId, token_id: &TokenId, bidder_id: &AccountId) -> Offer {
+        let contract_and_token_id = format!("{}:{}", contract_id, token_id);
+        let offer = self.offers.remove(&offer_id(contract_id, token_id, bidder_id)).expect("No offer");
+
+        let mut bidder_ids = self.offers_by_token.get(&contract_and_token_id).expect("No offers for token");
+        bidder_ids.remove(bidder_id);
+        if bidder_ids.is_empty() {
+            self.offers_by_token.remove(&contract_and_token_id);
+        } else {
+            self.offers_by_token.insert(&contract_and_token_id, &bidder_ids);
+        }
+
+        let mut contract_and_token_ids = self.offers_by_bidder.get(bidder_id).expect("No offers for bidder");
+        contract_and_token_ids.remove(&contract_and_token_id);
+        if contract_and_token_ids.is_empty() {
+            self.offers_by_bidder.remove(bidder_id);
+        } else {
+            self.offers_by_bidder.insert(bidder_id, &contract_and_token_ids);
+        }
+        offer
+    }
+}
+
+fn offer_id(contract_id: &AccountId, token_id: &TokenId, bidder_id: &AccountId) -> String {
+    format!("{}:{}:{}", contract_id, token_id, bidder_id)
+}
@@ -0,0 +1,108 @@
+use crate::*;
+
+/// taken out of the seller's available storage balance for whoever prunes the sale
+/// capped so the seller's other listings stay covered
+const PRUNE_BOUNTY: Balance = 1_000_000_000_000_000_000_000;
+/// each sale that isn't expired costs a cross-contract approval check
+const MAX_PRUNE_LIMIT: u64 = 10;
+const GAS_FOR_NFT_IS_APPROVED: Gas = 5_000_000_000_000;
+const GAS_FOR_ON_PRUNE_CHECK: Gas = 10_000_000_000_000;
+
+impl Sale {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map(|expires_at| env::block_timestamp() >= expires_at.0).unwrap_or(false)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// permissionless cleanup of the sales in [from_index, from_index + limit)
+    /// expired sales are removed right away, the rest are removed if the market lost its approval
+    /// (e.g. the seller transferred the token) once the token contract answers
+    pub fn prune_sales(&mut self, from_index: Option<U128>, limit: Option<u64>) -> u64 {
+        let pruner_id = env::predecessor_account_id();
+        let start = u128::from(from_index.unwrap_or(U128(0))) as u64;
+        let limit = std::cmp::min(limit.unwrap_or(MAX_PRUNE_LIMIT), MAX_PRUNE_LIMIT);
+        let values = self.sales.values_as_vector();
+        let sales: Vec<Sale> = (start..std::cmp::min(start + limit, values.len()))
+            .map(|index| values.get(index).unwrap())
+            .filter(|sale| !sale.processing && !sale.has_bids())
+            .collect();
+
+        let mut pruned = 0;
+        for sale in sales {
+            if sale.is_expired() {
+                self.internal_prune_sale(sale.nft_contract_id, sale.token_id, pruner_id.clone());
+                pruned += 1;
+            } else {
+                ext_transfer::nft_is_approved(
+                    sale.token_id.clone(),
+                    ValidAccountId::try_from(env::current_account_id()).unwrap(),
+                    Some(sale.approval_id),
+                    &sale.nft_contract_id,
+                    NO_DEPOSIT,
+                    GAS_FOR_NFT_IS_APPROVED,
+                ).then(ext_self::on_prune_check(
+                    sale.nft_contract_id,
+                    sale.token_id,
+                    sale.approval_id,
+                    pruner_id.clone(),
+                    &env::current_account_id(),
+                    NO_DEPOSIT,
+                    GAS_FOR_ON_PRUNE_CHECK,
+                ));
+            }
+        }
+        pruned
+    }
+
+    /// self callback
+
+    pub fn on_prune_check(
+        &mut self,
+        token_contract_id: AccountId,
+        token_id: TokenId,
+        approval_id: u64,
+        pruner_id: AccountId,
+    ) -> bool {
+        assert_eq!(
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            "Method is private"
+        );
+        let is_approved = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<bool>(&value).unwrap_or(true),
+            // e.g. the token was burned and nft_is_approved panicked, the sale can't be bought anymore
+            _ => false,
+        };
+        if is_approved {
+            return false;
+        }
+        // the sale may have been relisted, bought or bid on while the check was in flight
+        match self.sales.get(&format!("{}:{}", token_contract_id, token_id)) {
+            Some(sale) if sale.approval_id == approval_id && !sale.processing && !sale.has_bids() => {
+                self.internal_prune_sale(token_contract_id, token_id, pruner_id);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Contract {
+    fn internal_prune_sale(&mut self, contract_id: AccountId, token_id: TokenId, pruner_id: AccountId) {
+        let sale = self.internal_take_sale(&contract_id, &token_id);
+        let bounty = self.internal_withdraw_storage(&sale.owner_id, PRUNE_BOUNTY);
+        if bounty > 0 {
+            Promise::new(pruner_id).transfer(bounty);
+        }
+
+        MarketEventKind::SaleRemoved(vec![SaleLog {
+            nft_contract_id: contract_id,
+            token_id,
+            owner_id: sale.owner_id,
+            price: sale.price,
+        }])
+        .emit();
+    }
+}
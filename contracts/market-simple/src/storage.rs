@@ -0,0 +1,168 @@
+use crate::*;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+/// NEP-145, a seller's balance covers storage for all of their active listings
+pub trait StorageManagement {
+    fn storage_deposit(&mut self, account_id: Option<ValidAccountId>) -> StorageBalance;
+
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance;
+
+    /// `min` is the storage cost of a single listing
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds;
+
+    fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance>;
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    #[payable]
+    fn storage_deposit(&mut self, account_id: Option<ValidAccountId>) -> StorageBalance {
+        let account_id = account_id
+            .map(|a| a.into())
+            .unwrap_or_else(env::predecessor_account_id);
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "Requires attached deposit");
+        if self.storage_deposits.get(&account_id).is_none() {
+            let min = self.storage_per_sale_cost();
+            assert!(
+                amount >= min,
+                "Requires a deposit of at least {} yoctoNEAR to register, the cost of one listing",
+                min
+            );
+        }
+        self.internal_deposit_storage(&account_id, amount);
+        self.internal_storage_balance(&account_id)
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let total = self.storage_deposits.get(&account_id).expect("The account is not registered");
+        let available = total - self.internal_storage_used(&account_id);
+        let amount = amount.map(|a| a.into()).unwrap_or(available);
+        assert!(
+            amount <= available,
+            "The amount is greater than the available storage balance"
+        );
+        if total == amount {
+            self.storage_deposits.remove(&account_id);
+        } else {
+            self.storage_deposits.insert(&account_id, &(total - amount));
+        }
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+        self.internal_storage_balance(&account_id)
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: self.storage_per_sale_cost().into(),
+            max: None,
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.storage_deposits
+            .get(account_id.as_ref())
+            .map(|_| self.internal_storage_balance(account_id.as_ref()))
+    }
+}
+
+impl Contract {
+    /// bytes for the largest sale `internal_add_sale` accepts, including a new owner's index
+    /// an auction with a bid and a full `ft_prices` can't be listed together, so this is an upper bound
+    pub(crate) fn measure_storage_per_sale(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id = "a".repeat(64);
+        let tmp_token_id = "t".repeat(MAX_TOKEN_ID_LENGTH);
+        let ft_prices = (0..MAX_FT_PRICES)
+            .map(|i| (format!("{}{}", i, "f".repeat(63)), U128(u128::MAX)))
+            .collect();
+        let sale = Sale {
+            nft_contract_id: tmp_account_id.clone(),
+            token_id: tmp_token_id.clone(),
+            owner_id: tmp_account_id.clone(),
+            approval_id: u64::MAX,
+            beneficiary: tmp_account_id.clone(),
+            price: U128(u128::MAX),
+            processing: false,
+            auction: Some(Auction {
+                min_increment: U128(u128::MAX),
+                start_at: U64(u64::MAX),
+                end_at: U64(u64::MAX),
+                extension: U64(u64::MAX),
+                bidder_id: Some(tmp_account_id.clone()),
+                bid: U128(u128::MAX),
+            }),
+            dutch_auction: None,
+            ft_prices,
+            expires_at: Some(U64(u64::MAX)),
+        };
+        self.sales.insert(&format!("{}:{}", tmp_account_id, tmp_token_id), &sale);
+        self.internal_index_sale(&sale);
+        self.storage_per_sale = env::storage_usage() - initial_storage_usage;
+        self.internal_take_sale(&tmp_account_id, &tmp_token_id);
+    }
+
+    pub(crate) fn storage_per_sale_cost(&self) -> Balance {
+        Balance::from(self.storage_per_sale) * env::storage_byte_cost()
+    }
+
+    /// what the account's active listings cost
+    pub(crate) fn internal_storage_used(&self, account_id: &AccountId) -> Balance {
+        let listings = self.by_owner_id.get(account_id).map(|sales_set| sales_set.len()).unwrap_or(0);
+        Balance::from(listings) * self.storage_per_sale_cost()
+    }
+
+    /// takes up to `amount` of the account's available storage balance, returns what was taken
+    pub(crate) fn internal_withdraw_storage(&mut self, account_id: &AccountId, amount: Balance) -> Balance {
+        let total = self.storage_deposits.get(account_id).unwrap_or(0);
+        let available = total.saturating_sub(self.internal_storage_used(account_id));
+        let amount = std::cmp::min(amount, available);
+        if total == amount {
+            self.storage_deposits.remove(account_id);
+        } else {
+            self.storage_deposits.insert(account_id, &(total - amount));
+        }
+        amount
+    }
+
+    pub(crate) fn internal_deposit_storage(&mut self, account_id: &AccountId, amount: Balance) {
+        let total = self.storage_deposits.get(account_id).unwrap_or(0);
+        self.storage_deposits.insert(account_id, &(total + amount));
+    }
+
+    /// called after a listing was added
+    pub(crate) fn internal_assert_storage(&self, account_id: &AccountId) {
+        let total = self.storage_deposits.get(account_id).unwrap_or(0);
+        let used = self.internal_storage_used(account_id);
+        assert!(
+            total >= used,
+            "Requires {} more yoctoNEAR in storage balance or attached deposit to cover all listings",
+            used - total
+        );
+    }
+
+    fn internal_storage_balance(&self, account_id: &AccountId) -> StorageBalance {
+        let total = self.storage_deposits.get(account_id).unwrap_or(0);
+        StorageBalance {
+            total: total.into(),
+            available: total.saturating_sub(self.internal_storage_used(account_id)).into(),
+        }
+    }
+}
@@ -4,3 +4,5 @@ set -e
 RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release
 mkdir -p ../../out
 cp target/wasm32-unknown-unknown/release/*.wasm ../../out/main.wasm
+# the factory embeds this build to deploy collections
+cp target/wasm32-unknown-unknown/release/*.wasm res/nft_simple.wasm
@@ -0,0 +1,50 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// owner (or an approved account if the contract allows it) destroys the token
+    #[payable]
+    pub fn nft_burn(&mut self, token_id: TokenId) {
+        assert_one_yocto();
+        self.internal_burn(&env::predecessor_account_id(), &token_id);
+    }
+
+    pub fn set_approved_can_burn(&mut self, approved_can_burn: bool) {
+        self.assert_owner();
+        self.approved_can_burn = approved_can_burn;
+    }
+
+    pub fn get_approved_can_burn(&self) -> bool {
+        self.approved_can_burn
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_burn(&mut self, sender_id: &AccountId, token_id: &TokenId) {
+        let token = self.tokens_by_id.get(token_id).expect("Token not found");
+        let authorized_id = if sender_id != &token.owner_id {
+            if !self.approved_can_burn || !token.approved_account_ids.contains_key(sender_id) {
+                env::panic(b"Unauthorized");
+            }
+            Some(sender_id.clone())
+        } else {
+            None
+        };
+
+        self.tokens_by_id.remove(token_id);
+        self.internal_remove_token_from_owner(&token.owner_id, token_id);
+        self.guest_sales.remove(token_id);
+        self.total_supply -= 1;
+
+        self.internal_release_storage(&token.storage_payer_id, token.storage_used);
+        self.internal_refund_approved_account_ids(&token.owner_id, &token.approved_account_ids);
+
+        NftEventKind::NftBurn(vec![NftBurnLog {
+            authorized_id,
+            owner_id: token.owner_id,
+            token_ids: vec![token_id.clone()],
+            memo: None,
+        }])
+        .emit();
+    }
+}
@@ -0,0 +1,57 @@
+use crate::*;
+use near_sdk::json_types::U64;
+
+pub trait NonFungibleTokenEnumeration {
+    fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonToken>;
+
+    fn nft_supply_for_owner(&self, account_id: ValidAccountId) -> U64;
+
+    fn nft_tokens_for_owner(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken>;
+}
+
+#[near_bindgen]
+impl NonFungibleTokenEnumeration for Contract {
+    fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonToken> {
+        let start = u128::from(from_index.unwrap_or(U128(0))) as u64;
+        let keys = self.tokens_by_id.keys_as_vector();
+        let values = self.tokens_by_id.values_as_vector();
+        (start..std::cmp::min(start + limit.unwrap_or(DEFAULT_PAGE_LIMIT), keys.len()))
+            .map(|index| JsonToken::new(keys.get(index).unwrap(), values.get(index).unwrap()))
+            .collect()
+    }
+
+    fn nft_supply_for_owner(&self, account_id: ValidAccountId) -> U64 {
+        self.tokens_per_owner
+            .get(account_id.as_ref())
+            .map(|tokens_set| tokens_set.len())
+            .unwrap_or(0)
+            .into()
+    }
+
+    fn nft_tokens_for_owner(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        let tokens_set = if let Some(tokens_set) = self.tokens_per_owner.get(account_id.as_ref()) {
+            tokens_set
+        } else {
+            return vec![];
+        };
+        let start = u128::from(from_index.unwrap_or(U128(0))) as u64;
+        let keys = tokens_set.as_vector();
+        (start..std::cmp::min(start + limit.unwrap_or(DEFAULT_PAGE_LIMIT), keys.len()))
+            .map(|index| {
+                let token_id = keys.get(index).unwrap();
+                let token = self.tokens_by_id.get(&token_id).unwrap();
+                JsonToken::new(token_id, token)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,85 @@
+use crate::*;
+
+/// what a sponsored guest may do, the contract-wide policy applies unless the guest was added with its own
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GuestPolicy {
+    pub mint_limit: u8,
+    pub sale_limit: u8,
+    pub transfer_limit: u8,
+    /// upper bound of storage the contract sponsors per guest mint
+    pub max_token_id_length: u32,
+    pub max_metadata_bytes: u32,
+    /// kept by the contract when a guest upgrades to a real account
+    pub sponsor_fee: U128,
+    /// kept by the contract when a guest withdraws to an existing account
+    pub withdraw_fee: U128,
+    pub access_key_allowance: U128,
+    /// nanoseconds a guest can mint, sell and transfer after being added, forever if None
+    pub expiry: Option<U64>,
+}
+
+impl Default for GuestPolicy {
+    fn default() -> Self {
+        Self {
+            mint_limit: 3,
+            sale_limit: 3,
+            transfer_limit: 3,
+            max_token_id_length: 256,
+            max_metadata_bytes: 1024,
+            sponsor_fee: U128(100_000_000_000_000_000_000_000),
+            withdraw_fee: U128(0),
+            access_key_allowance: U128(100_000_000_000_000_000_000_000),
+            expiry: None,
+        }
+    }
+}
+
+/// the quota a guest call counts against
+pub(crate) enum GuestAction {
+    Mint,
+    Sale,
+    Transfer,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn set_guest_policy(&mut self, guest_policy: GuestPolicy) {
+        self.assert_owner();
+        self.guest_policy = guest_policy;
+    }
+
+    pub fn get_guest_policy(&self) -> GuestPolicy {
+        self.guest_policy.clone()
+    }
+}
+
+impl Guest {
+    /// counts the action against the guest's quota, panics once the limit is reached
+    pub(crate) fn count(&mut self, action: GuestAction, policy: &GuestPolicy) {
+        let (count, limit, name) = match action {
+            GuestAction::Mint => (&mut self.mints, policy.mint_limit, "mint"),
+            GuestAction::Sale => (&mut self.sales, policy.sale_limit, "sale"),
+            GuestAction::Transfer => (&mut self.transfers, policy.transfer_limit, "transfer"),
+        };
+        assert!(*count < limit, "Exceeded guest {} limit {}", name, limit);
+        *count += 1;
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_guest_policy(&self, guest: &Guest) -> GuestPolicy {
+        guest.policy.clone().unwrap_or_else(|| self.guest_policy.clone())
+    }
+
+    /// the signer's guest record and the policy it is held to, panics if the guest has expired
+    pub(crate) fn signer_guest(&self) -> (PublicKey, Guest, GuestPolicy) {
+        let signer_id = env::signer_account_pk();
+        let guest = self.guests.get(&signer_id).expect("Not a guest");
+        if let Some(expires_at) = guest.expires_at {
+            assert!(env::block_timestamp() < expires_at.0, "Guest expired");
+        }
+        let policy = self.internal_guest_policy(&guest);
+        (signer_id, guest, policy)
+    }
+}
@@ -1,9 +1,5 @@
 use crate::*;
 
-/// Price per 1 byte of storage from mainnet config after `1.18.0` release and protocol version `42`.
-/// It's 10 times lower than the genesis price.
-pub(crate) const STORAGE_PRICE_PER_BYTE: Balance = 10_000_000_000_000_000_000;
-
 pub(crate) fn unique_prefix(account_id: &AccountId) -> Vec<u8> {
     let mut prefix = Vec::with_capacity(33);
     prefix.push(b'o');
@@ -19,6 +15,13 @@ pub(crate) fn assert_one_yocto() {
     )
 }
 
+pub(crate) fn assert_at_least_one_yocto() {
+    assert!(
+        env::attached_deposit() >= 1,
+        "Requires attached deposit of at least 1 yoctoNEAR"
+    )
+}
+
 pub(crate) fn assert_self() {
     assert_eq!(
         env::predecessor_account_id(),
@@ -27,36 +30,11 @@ pub(crate) fn assert_self() {
     );
 }
 
-pub(crate) fn deposit_refund(storage_used: u64) {
-    let required_cost = STORAGE_PRICE_PER_BYTE * Balance::from(storage_used);
-    let attached_deposit = env::attached_deposit();
-
-    assert!(
-        required_cost <= attached_deposit,
-        "Requires to attach {} NEAR tokens to cover storage",
-        required_cost
-    );
-
-    let refund = attached_deposit - required_cost;
-    if refund > 0 {
-        Promise::new(env::predecessor_account_id()).transfer(refund);
-    }
-}
-
+/// Matches the storage delta measured in `nft_approve`, used when approvals are cleared in bulk.
 pub(crate) fn bytes_for_approved_account_id(account_id: &AccountId) -> u64 {
-    // The extra 4 bytes are coming from Borsh serialization to store the length of the string.
-    account_id.len() as u64 + 4
-}
-
-pub(crate) fn refund_approved_account_ids(
-    account_id: AccountId,
-    approved_account_ids: &HashSet<AccountId>,
-) -> Promise {
-    let storage_released: u64 = approved_account_ids
-        .iter()
-        .map(bytes_for_approved_account_id)
-        .sum();
-    Promise::new(account_id).transfer(Balance::from(storage_released) * STORAGE_PRICE_PER_BYTE)
+    // The extra 4 bytes are coming from Borsh serialization to store the length of the string,
+    // the extra 8 bytes are the approval id.
+    account_id.len() as u64 + 4 + 8
 }
 
 impl Contract {
@@ -68,19 +46,39 @@ impl Contract {
         );
     }
 
+    /// approvals were paid by whoever owned the token when they were added
+    pub(crate) fn internal_refund_approved_account_ids(
+        &mut self,
+        account_id: &AccountId,
+        approved_account_ids: &HashMap<AccountId, u64>,
+    ) {
+        let storage_released: u64 = approved_account_ids
+            .keys()
+            .map(bytes_for_approved_account_id)
+            .sum();
+        self.internal_release_storage(account_id, storage_released);
+    }
+
+    /// returns the bytes of the owner's token set if this created it, for the caller to charge
     pub(crate) fn internal_add_token_to_owner(
         &mut self,
         account_id: &AccountId,
         token_id: &TokenId,
-    ) {
-        let mut tokens_set = self
-            .tokens_per_owner
-            .get(account_id)
-            .unwrap_or_else(|| UnorderedSet::new(unique_prefix(account_id)));
+    ) -> StorageUsage {
+        let initial_storage_usage = env::storage_usage();
+        let mut tokens_set = self.tokens_per_owner.get(account_id).unwrap_or_else(|| {
+            let tokens_set = UnorderedSet::new(unique_prefix(account_id));
+            self.tokens_per_owner.insert(account_id, &tokens_set);
+            tokens_set
+        });
+        let set_storage_usage = env::storage_usage() - initial_storage_usage;
         tokens_set.insert(token_id);
         self.tokens_per_owner.insert(account_id, &tokens_set);
+        set_storage_usage
     }
 
+    /// an emptied set is removed and its storage credited back to the owner,
+    /// guests have no storage balance and keep their set until `remove_guest`
     pub(crate) fn internal_remove_token_from_owner(
         &mut self,
         account_id: &AccountId,
@@ -91,13 +89,29 @@ impl Contract {
             .get(account_id)
             .expect("Token should be owned by the sender");
         tokens_set.remove(token_id);
-        if tokens_set.is_empty() {
+        if tokens_set.is_empty() && self.storage_accounts.contains_key(account_id) {
+            let initial_storage_usage = env::storage_usage();
             self.tokens_per_owner.remove(account_id);
+            self.internal_release_storage(account_id, initial_storage_usage - env::storage_usage());
         } else {
             self.tokens_per_owner.insert(account_id, &tokens_set);
         }
     }
 
+    /// sale was made on behalf of a guest, give the guest the proceeds of the sale (or the refunded listing storage)
+    pub(crate) fn internal_credit_guest_sale(
+        &mut self,
+        token_id: &TokenId,
+        guest_sale: &GuestSale,
+        amount: Balance,
+    ) {
+        let mut guest = self.guests.get(&guest_sale.public_key).expect("No guest");
+        let new_balance = u128::from(guest.balance) + amount;
+        guest.balance = U128(new_balance);
+        self.guests.insert(&guest_sale.public_key, &guest);
+        self.guest_sales.remove(token_id);
+    }
+
     pub(crate) fn internal_transfer(
         &mut self,
         sender_id: &AccountId,
@@ -105,23 +119,28 @@ impl Contract {
         token_id: &TokenId,
         enforce_approval_id: Option<u64>,
         memo: Option<String>,
-    ) -> (AccountId, HashSet<AccountId>) {
+    ) -> (AccountId, HashMap<AccountId, u64>) {
         let Token {
             owner_id,
             metadata,
             approved_account_ids,
-            approval_id,
+            next_approval_id,
+            royalty,
+            storage_payer_id,
+            storage_used,
         } = self.tokens_by_id.get(token_id).expect("Token not found");
-        if sender_id != &owner_id && !approved_account_ids.contains(sender_id) {
-            env::panic(b"Unauthorized");
-        }
-
-        if let Some(enforce_approval_id) = enforce_approval_id {
-            assert_eq!(
-                approval_id,
-                enforce_approval_id,
-                "The token approval_id is different from provided"
-            );
+        if sender_id != &owner_id {
+            let approval_id = approved_account_ids
+                .get(sender_id)
+                .unwrap_or_else(|| env::panic(b"Unauthorized"));
+            // the sender's own approval must be the one it was given, not a stale or newer one
+            if let Some(enforce_approval_id) = enforce_approval_id {
+                assert_eq!(
+                    approval_id,
+                    &enforce_approval_id,
+                    "The token approval_id is different from provided"
+                );
+            }
         }
 
         assert_ne!(
@@ -129,28 +148,37 @@ impl Contract {
             "The token owner and the receiver should be different"
         );
 
-        env::log(
-            format!(
-                "Transfer {} from @{} to @{}",
-                token_id, &owner_id, receiver_id
-            )
-            .as_bytes(),
-        );
-
         self.internal_remove_token_from_owner(&owner_id, token_id);
-        self.internal_add_token_to_owner(receiver_id, token_id);
+        // the receiver pays for its token set, so unregistered receivers need a storage deposit first
+        let set_storage_usage = self.internal_add_token_to_owner(receiver_id, token_id);
+        if set_storage_usage > 0 {
+            self.internal_charge_storage(receiver_id, set_storage_usage, 0);
+        }
 
         let token = Token {
             owner_id: receiver_id.clone(),
             metadata,
             approved_account_ids: Default::default(),
-            approval_id: approval_id + 1,
+            next_approval_id,
+            royalty,
+            storage_payer_id,
+            storage_used,
         };
         self.tokens_by_id.insert(token_id, &token);
 
-        if let Some(memo) = memo {
-            env::log(format!("Memo: {}", memo).as_bytes());
-        }
+        let authorized_id = if sender_id != &owner_id {
+            Some(sender_id.clone())
+        } else {
+            None
+        };
+        NftEventKind::NftTransfer(vec![NftTransferLog {
+            authorized_id,
+            old_owner_id: owner_id.clone(),
+            new_owner_id: receiver_id.clone(),
+            token_ids: vec![token_id.clone()],
+            memo,
+        }])
+        .emit();
 
         (owner_id, approved_account_ids)
     }
@@ -1,40 +1,82 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
-use near_sdk::json_types::{U128, ValidAccountId, Base58PublicKey};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::json_types::{U128, U64, ValidAccountId, Base58PublicKey};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{env, ext_contract, near_bindgen, Gas, PublicKey, AccountId, Balance, PanicOnDefault, Promise, PromiseResult, StorageUsage};
+use nft_events::{GuestEventKind, GuestWithdrawLog, NftBurnLog, NftEventKind, NftMintLog, NftTransferLog};
 
 use crate::internal::*;
+pub use crate::burn::*;
+pub use crate::enumeration::*;
+pub use crate::guest_policy::*;
+pub use crate::metadata::*;
 pub use crate::mint::*;
 pub use crate::nft_core::*;
+pub use crate::royalty::*;
+pub use crate::storage::*;
 
+mod burn;
+mod enumeration;
+mod guest_policy;
 mod internal;
+mod metadata;
 mod mint;
 mod nft_core;
+mod royalty;
+mod storage;
 
 #[global_allocator]
 static ALLOC: near_sdk::wee_alloc::WeeAlloc<'_> = near_sdk::wee_alloc::WeeAlloc::INIT;
 
 const ON_CALLBACK_GAS: u64 = 20_000_000_000_000;
+/// `on_guest_withdrawn` moves each token back if the withdrawal failed
+const GAS_PER_RESTORED_TOKEN: Gas = 5_000_000_000_000;
+const MAX_WITHDRAW_TOKENS: usize = 10;
+/// each removed token may delist at its markets and moves or burns the token
+const MAX_REMOVE_GUEST_TOKENS: usize = 10;
 const GAS_FOR_RESOLVE_TRANSFER: Gas = 10_000_000_000_000;
 const GAS_FOR_NFT_TRANSFER_CALL: Gas = 25_000_000_000_000 + GAS_FOR_RESOLVE_TRANSFER;
+const GAS_FOR_REMOVE_SALE: Gas = 10_000_000_000_000;
 const NO_DEPOSIT: Balance = 0;
-const GUEST_STRING_LENGTH_LIMIT: usize = 256;
-const GUEST_MINT_LIMIT: u8 = 3;
 const MAX_MARKET_DEPOSIT: u128 = 100_000_000_000_000_000_000_000;
-const ACCESS_KEY_ALLOWANCE: u128 = 100_000_000_000_000_000_000_000;
-const SPONSOR_FEE: u128 = 100_000_000_000_000_000_000_000;
+const DEFAULT_PAGE_LIMIT: u64 = 50;
 pub type TokenId = String;
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Token {
     pub owner_id: AccountId,
-    pub metadata: String,
-    pub approved_account_ids: HashSet<AccountId>,
-    pub approval_id: u64,
+    pub metadata: TokenMetadata,
+    /// approved account -> the approval id it was given
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    pub next_approval_id: u64,
+    pub royalty: Royalty,
+    /// who is credited the token's storage when it is burned
+    pub storage_payer_id: AccountId,
+    pub storage_used: StorageUsage,
+}
+
+/// token as returned by view methods, `Token` itself doesn't store its own id
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonToken {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub metadata: TokenMetadata,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+}
+
+impl JsonToken {
+    pub fn new(token_id: TokenId, token: Token) -> Self {
+        Self {
+            token_id,
+            owner_id: token.owner_id,
+            metadata: token.metadata,
+            approved_account_ids: token.approved_account_ids,
+        }
+    }
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
@@ -42,7 +84,25 @@ pub struct Token {
 pub struct Guest {
     pub account_id: AccountId,
     pub mints: u8,
+    pub sales: u8,
+    pub transfers: u8,
     pub balance: U128,
+    /// overrides the contract's guest policy for this guest
+    pub policy: Option<GuestPolicy>,
+    pub expires_at: Option<U64>,
+}
+
+/// what happens to a guest's tokens when the guest is removed
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum GuestRemoval {
+    /// tokens go to receiver_id, and the guest's balance unless `balance_receiver_id` is given
+    Transfer { receiver_id: ValidAccountId },
+    /// tokens are burned
+    Burn,
+    /// panics if the guest still owns tokens
+    Refuse,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
@@ -65,91 +125,120 @@ pub struct Contract {
 
     pub total_supply: u64,
 
-    /// The storage size in bytes for one account.
-    pub extra_storage_in_bytes_per_token: StorageUsage,
+    /// NEP-145 storage balances, every state-growing method debits the payer's entry
+    pub storage_accounts: LookupMap<AccountId, AccountStorage>,
+    /// The storage size in bytes of one storage_accounts entry.
+    pub account_storage_usage: StorageUsage,
 
-    /// custom fields for guests and example app (with no backend need to store list of tokens)
+    pub metadata: LazyOption<NFTContractMetadata>,
+
+    /// whether approved accounts may burn tokens on behalf of the owner
+    pub approved_can_burn: bool,
+
+    /// collection settings, e.g. chosen by a creator launching through the factory
+    pub default_royalty: Royalty,
+    pub minting_policy: MintingPolicy,
+
+    /// custom fields for guests
     pub guests: LookupMap<PublicKey, Guest>,
     pub guest_sales: LookupMap<TokenId, GuestSale>,
-    /// this is lazy, could also store list of owners and query tokens_per_owner for each owner
-    pub token_ids: Vec<TokenId>,
+    pub guest_policy: GuestPolicy,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
-    pub fn new(owner_id: ValidAccountId) -> Self {
+    pub fn new(
+        owner_id: ValidAccountId,
+        metadata: NFTContractMetadata,
+        default_royalty: Option<Royalty>,
+        minting_policy: Option<MintingPolicy>,
+        guest_policy: Option<GuestPolicy>,
+    ) -> Self {
         assert!(!env::state_exists(), "Already initialized");
+        metadata.assert_valid();
+        let default_royalty = default_royalty.unwrap_or_default();
+        assert_valid_royalty(&default_royalty);
         let mut this = Self {
             tokens_per_owner: LookupMap::new(b"a".to_vec()),
             tokens_by_id: UnorderedMap::new(b"t".to_vec()),
             guests: LookupMap::new(b"g".to_vec()),
             guest_sales: LookupMap::new(b"m".to_vec()),
+            guest_policy: guest_policy.unwrap_or_default(),
             owner_id: owner_id.into(),
-            token_ids: Vec::new(),
             total_supply: 0,
-            extra_storage_in_bytes_per_token: 0,
+            storage_accounts: LookupMap::new(b"s".to_vec()),
+            account_storage_usage: 0,
+            metadata: LazyOption::new(b"n".to_vec(), Some(&metadata)),
+            approved_can_burn: false,
+            default_royalty,
+            minting_policy: minting_policy.unwrap_or(MintingPolicy::Open),
         };
-        this.measure_min_token_storage_cost();
+        this.measure_account_storage_usage();
         this
     }
 
-    fn measure_min_token_storage_cost(&mut self) {
-        let initial_storage_usage = env::storage_usage();
-        let tmp_account_id = "a".repeat(64);
-        let u = UnorderedSet::new(unique_prefix(&tmp_account_id));
-        self.tokens_per_owner.insert(&tmp_account_id, &u);
-
-        let tokens_per_owner_entry_in_bytes = env::storage_usage() - initial_storage_usage;
-        let owner_id_extra_cost_in_bytes = (tmp_account_id.len() - self.owner_id.len()) as u64;
-
-        self.extra_storage_in_bytes_per_token =
-            tokens_per_owner_entry_in_bytes + owner_id_extra_cost_in_bytes;
-
-        self.tokens_per_owner.remove(&tmp_account_id);
-    }
-
     /// non-standard methods for guest and free mint/approval management
 
     /// guest mint restricts token ID and metadata size 
     /// contract needs to know upper bound of storage it will sponsor
-    /// guests are limited mints and approvals
-    pub fn nft_mint_guest(&mut self, token_id: TokenId, metadata: String) {
+    /// guests are limited mints, sales and transfers by their guest policy
+    pub fn nft_mint_guest(&mut self, token_id: TokenId, metadata: TokenMetadata) {
+        let (signer_id, mut guest, policy) = self.signer_guest();
         assert!(
-            token_id.len() < GUEST_STRING_LENGTH_LIMIT,
+            token_id.len() <= policy.max_token_id_length as usize,
             "Token ID too long for guest mint"
         );
+        metadata.assert_valid();
         assert!(
-            metadata.len() < GUEST_STRING_LENGTH_LIMIT,
+            metadata.try_to_vec().unwrap().len() <= policy.max_metadata_bytes as usize,
             "Metadata too long for guest mint"
         );
-        let guest = self.admin_guest(1);
+        assert!(self.tokens_by_id.get(&token_id).is_none(), "Token already exists");
+        guest.count(GuestAction::Mint, &policy);
+        self.guests.insert(&signer_id, &guest);
         let owner_id = guest.account_id;
         let token = Token {
             owner_id,
             metadata,
             approved_account_ids: Default::default(),
-            approval_id: 0,
+            next_approval_id: 0,
+            royalty: Default::default(),
+            storage_payer_id: env::current_account_id(),
+            storage_used: 0,
         };
         assert!(
             self.tokens_by_id.insert(&token_id, &token).is_none(),
             "Token already exists"
         );
-        self.internal_add_token_to_owner(&token.owner_id, &token_id);
+        // guests keep the set `add_guest` sponsored, this only charges if it went missing
+        let set_storage_usage = self.internal_add_token_to_owner(&token.owner_id, &token_id);
+        if set_storage_usage > 0 {
+            let owner_id = self.owner_id.clone();
+            self.internal_charge_storage(&owner_id, set_storage_usage, 0);
+        }
         self.total_supply += 1;
-        self.token_ids.push(token_id);
+
+        NftEventKind::NftMint(vec![NftMintLog {
+            owner_id: token.owner_id,
+            token_ids: vec![token_id],
+            memo: None,
+        }])
+        .emit();
     }
 
     pub fn nft_add_sale_guest(&mut self, token_id: TokenId, price: U128, market_id: ValidAccountId, market_deposit: U128) {
         let deposit: Balance = market_deposit.into();
         assert!(deposit <= MAX_MARKET_DEPOSIT, "Cannot make market deposits more than {}", MAX_MARKET_DEPOSIT);
-        let guest = self.admin_guest(0);
+        let (signer_id, mut guest, policy) = self.signer_guest();
         let token = self.tokens_by_id.get(&token_id).expect("Token not found");
         assert_eq!(&guest.account_id, &token.owner_id);
         assert_eq!(token.approved_account_ids.len(), 0, "Can only approve one market at a time as guest");
+        guest.count(GuestAction::Sale, &policy);
+        self.guests.insert(&signer_id, &guest);
         let market_contract: AccountId = market_id.clone().into();
         let sale = GuestSale {
-            public_key: env::signer_account_pk(),
+            public_key: signer_id,
             price: price.clone().into(),
             deposit: deposit.clone()
         };
@@ -158,10 +247,11 @@ impl Contract {
             current_account_id.clone(),
             token_id.clone(),
             token.owner_id,
-            token.approval_id,
+            token.next_approval_id,
             Some(format!("{{\"beneficiary\":\"{}\",\"price\":\"{}\"}}", current_account_id, u128::from(price).to_string())),
             &market_contract,
-            MAX_MARKET_DEPOSIT,
+            // tops up the guest's storage balance at the market, can be 0 once it covers their listings
+            deposit,
             env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL,
         ).then(ext_self::on_market_updated(
             token_id,
@@ -173,43 +263,46 @@ impl Contract {
         ));
     }
 
-    pub fn nft_remove_sale_guest(&mut self, token_id: TokenId, market_id: ValidAccountId) {
-        let guest = self.admin_guest(0);
+    /// the market refunds the listing's storage, which goes to the guest's balance
+    pub fn nft_remove_sale_guest(&mut self, token_id: TokenId, market_id: ValidAccountId) -> Promise {
+        let (_, guest, _) = self.signer_guest();
         let token = self.tokens_by_id.get(&token_id).expect("Token not found");
         assert_eq!(&guest.account_id, &token.owner_id);
-        let market_contract: AccountId = market_id.clone().into();
-        assert_eq!(token.approved_account_ids.len(), 1, "No sale at market {}", market_contract.clone());
-        // // TODO no nft_on_revoke anymore make market remove sale
-        // ext_non_fungible_approval_receiver::nft_on_revoke(
-        //     env::current_account_id(),
-        //     token_id.clone(),
-        //     token.owner_id,
-        //     None,
-        //     &market_contract,
-        //     NO_DEPOSIT,
-        //     env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL,
-        // ).then(ext_self::on_market_updated(
-        //     token_id,
-        //     market_contract,
-        //     None,
-        //     &env::current_account_id(),
-        //     NO_DEPOSIT,
-        //     GAS_FOR_RESOLVE_TRANSFER,
-        // ));
+        let market_contract: AccountId = market_id.into();
+        assert!(token.approved_account_ids.contains_key(&market_contract), "No sale at market {}", market_contract);
+        ext_market::remove_sale_on_behalf(
+            token_id.clone(),
+            &market_contract,
+            NO_DEPOSIT,
+            env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL,
+        ).then(ext_self::on_market_updated(
+            token_id,
+            market_contract,
+            None,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
     }
 
-    /// internal helpers for guest admin
-    
-    fn admin_guest(&mut self, new_mints: u8) -> Guest {
-        let signer_id = env::signer_account_pk();
-        let mut guest = self.guests.get(&signer_id).expect("Not a guest");
-        assert!(
-            guest.mints < GUEST_MINT_LIMIT,
-            "Exceeded guest mint limit {}", GUEST_MINT_LIMIT
-        );
-        guest.mints += new_mints;
+    /// guests can't attach the 1 yocto `nft_transfer` asks for, so the signer's guest key authorizes the transfer
+    /// any guest sale for the token is cleared and market approvals are refunded
+    pub fn nft_transfer_guest(&mut self, receiver_id: ValidAccountId, token_id: TokenId, memo: Option<String>) {
+        let (signer_id, mut guest, policy) = self.signer_guest();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        assert_eq!(&guest.account_id, &token.owner_id);
+        guest.count(GuestAction::Transfer, &policy);
         self.guests.insert(&signer_id, &guest);
-        guest
+
+        self.guest_sales.remove(&token_id);
+        let (previous_owner_id, approved_account_ids) = self.internal_transfer(
+            &guest.account_id,
+            receiver_id.as_ref(),
+            &token_id,
+            None,
+            memo,
+        );
+        self.internal_refund_approved_account_ids(&previous_owner_id, &approved_account_ids);
     }
 
     /// user wants to become a real NEAR account
@@ -220,18 +313,25 @@ impl Contract {
     ) -> Promise {
         let pk = env::signer_account_pk();
         let guest = self.guests.get(&pk).expect("No guest");
+        let policy = self.internal_guest_policy(&guest);
         let balance: Balance = guest.balance.into();
-        let fees = SPONSOR_FEE;
+        let fees: Balance = policy.sponsor_fee.into();
         assert!(balance > fees, "Not enough to upgrade");
-        env::log(format!("Withdrawing {} from contract", balance).as_bytes());
-        
+
         let account_id = guest.account_id;
+        GuestEventKind::GuestWithdrawn(vec![GuestWithdrawLog {
+            account_id: account_id.clone(),
+            receiver_id: account_id.clone(),
+            amount: U128(balance - fees),
+            token_ids: vec![],
+        }])
+        .emit();
         Promise::new(account_id.clone())
             .create_account()
             .add_full_access_key(public_key.into())
             .add_access_key(
                 access_key.into(),
-                ACCESS_KEY_ALLOWANCE,
+                policy.access_key_allowance.into(),
                 env::current_account_id(),
                 method_names.as_bytes().to_vec(),
             )
@@ -244,34 +344,207 @@ impl Contract {
             ))
     }
 
+    /// guest already has a NEAR account, their proceeds minus the withdraw fee and optionally their tokens go there
+    /// a failed transfer, e.g. the account doesn't exist, restores the balance and the tokens in the callback
+    pub fn withdraw_guest_to(&mut self, account_id: ValidAccountId, include_tokens: Option<bool>) -> Promise {
+        let pk = env::signer_account_pk();
+        let mut guest = self.guests.get(&pk).expect("No guest");
+        let receiver_id: AccountId = account_id.into();
+        assert_ne!(receiver_id, guest.account_id, "Cannot withdraw to the guest account");
+        let policy = self.internal_guest_policy(&guest);
+        let balance: Balance = guest.balance.into();
+        let fee: Balance = policy.withdraw_fee.into();
+        assert!(balance >= fee, "Not enough to withdraw");
+        guest.balance = U128(0);
+        self.guests.insert(&pk, &guest);
+
+        let token_ids = if include_tokens.unwrap_or(false) {
+            self.tokens_per_owner
+                .get(&guest.account_id)
+                .map(|tokens_set| tokens_set.to_vec())
+                .unwrap_or_default()
+        } else {
+            vec![]
+        };
+        // the callback has to be able to move every token back
+        assert!(
+            token_ids.len() <= MAX_WITHDRAW_TOKENS,
+            "Can withdraw at most {} tokens, transfer the rest first",
+            MAX_WITHDRAW_TOKENS
+        );
+        for token_id in &token_ids {
+            self.guest_sales.remove(token_id);
+            let (previous_owner_id, approved_account_ids) = self.internal_transfer(
+                &guest.account_id,
+                &receiver_id,
+                token_id,
+                None,
+                Some("guest withdrawn".to_string()),
+            );
+            self.internal_refund_approved_account_ids(&previous_owner_id, &approved_account_ids);
+        }
+
+        GuestEventKind::GuestWithdrawn(vec![GuestWithdrawLog {
+            account_id: guest.account_id,
+            receiver_id: receiver_id.clone(),
+            amount: U128(balance - fee),
+            token_ids: token_ids.clone(),
+        }])
+        .emit();
+        let callback_gas = ON_CALLBACK_GAS + GAS_PER_RESTORED_TOKEN * token_ids.len() as u64;
+        Promise::new(receiver_id.clone())
+            .transfer(balance - fee)
+            .then(ext_self::on_guest_withdrawn(
+                pk,
+                receiver_id,
+                U128(balance),
+                token_ids,
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                callback_gas,
+            ))
+    }
+
     /// only owner/backend API should be able to do this to avoid unwanted storage usage in creating new guest records
 
     /// add account_id to guests for get_predecessor and to storage to receive tokens
-    pub fn add_guest(&mut self, account_id: AccountId, public_key: Base58PublicKey) {
+    /// the guest record storage is paid from the owner's storage balance
+    /// `policy` overrides the contract's guest policy for this guest, its expiry starts now
+    #[payable]
+    pub fn add_guest(&mut self, account_id: AccountId, public_key: Base58PublicKey, policy: Option<GuestPolicy>) {
         assert_eq!(env::predecessor_account_id(), self.owner_id, "must be owner_id");
-        
+        let initial_storage_usage = env::storage_usage();
+
         if self.tokens_per_owner.get(&account_id).is_some() {
             env::panic(b"The account is already registered");
         }
 
+        let expires_at = policy
+            .as_ref()
+            .unwrap_or(&self.guest_policy)
+            .expiry
+            .map(|expiry| U64(env::block_timestamp() + expiry.0));
+
         let tokens_set = UnorderedSet::new(unique_prefix(&account_id));
         self.tokens_per_owner.insert(&account_id, &tokens_set);
         
         if self.guests.insert(&public_key.into(), &Guest{
             account_id,
             mints: 0,
+            sales: 0,
+            transfers: 0,
             balance: U128(0),
+            policy,
+            expires_at,
         }).is_some() {
             env::panic(b"guest account already added");
         }
+
+        let owner_id = self.owner_id.clone();
+        let refund = self.internal_charge_storage(
+            &owner_id,
+            env::storage_usage() - initial_storage_usage,
+            env::attached_deposit(),
+        );
+        if refund > 0 {
+            Promise::new(owner_id).transfer(refund);
+        }
     }
 
-    pub fn remove_guest(&mut self, public_key: Base58PublicKey) {
+    /// guest sales are cleared, delisted at their markets and approvals revoked along with the tokens, `mode` defaults to refuse
+    /// at most MAX_REMOVE_GUEST_TOKENS tokens are moved per call, returns false while the guest still owns tokens
+    /// once none are left the guest's balance goes to balance_receiver_id, defaulting to receiver_id when transferring
+    /// and the guest record storage goes back to the owner's storage balance
+    pub fn remove_guest(
+        &mut self,
+        public_key: Base58PublicKey,
+        mode: Option<GuestRemoval>,
+        balance_receiver_id: Option<ValidAccountId>,
+    ) -> bool {
         assert_eq!(env::predecessor_account_id(), self.owner_id, "must be owner_id");
-        let guest = self.guests.get(&public_key.clone().into()).expect("not a guest");
-        // TODO transfer NFTs
-        self.tokens_per_owner.remove(&guest.account_id);
-        self.guests.remove(&public_key.into());
+        let public_key: PublicKey = public_key.into();
+        let guest = self.guests.get(&public_key).expect("not a guest");
+        let account_id = guest.account_id.clone();
+        let (remaining, token_ids) = self
+            .tokens_per_owner
+            .get(&account_id)
+            .map(|tokens_set| {
+                let token_ids: Vec<TokenId> = tokens_set.iter().take(MAX_REMOVE_GUEST_TOKENS).collect();
+                (tokens_set.len() as usize, token_ids)
+            })
+            .unwrap_or_default();
+        let mode = mode.unwrap_or(GuestRemoval::Refuse);
+        if let GuestRemoval::Refuse = mode {
+            assert_eq!(remaining, 0, "Guest still owns {} tokens", remaining);
+        }
+        // the guest earned the balance, the owner has to say where it goes
+        let balance: Balance = guest.balance.into();
+        let balance_receiver_id: Option<AccountId> = balance_receiver_id.map(|a| a.into()).or_else(|| match &mode {
+            GuestRemoval::Transfer { receiver_id } => Some(receiver_id.clone().into()),
+            _ => None,
+        });
+        assert!(
+            balance == 0 || balance_receiver_id.is_some(),
+            "Guest still has a balance of {}, name a balance_receiver_id for it",
+            balance
+        );
+
+        // the guest can't delist once removed, the tokens are about to move anyway
+        for token_id in &token_ids {
+            let token = self.tokens_by_id.get(token_id).expect("Token not found");
+            for market_id in token.approved_account_ids.keys() {
+                ext_market::remove_sale_on_behalf(
+                    token_id.clone(),
+                    market_id,
+                    NO_DEPOSIT,
+                    GAS_FOR_REMOVE_SALE,
+                );
+            }
+        }
+
+        match &mode {
+            GuestRemoval::Transfer { receiver_id } => {
+                let receiver_id: AccountId = receiver_id.clone().into();
+                for token_id in &token_ids {
+                    self.guest_sales.remove(token_id);
+                    let (previous_owner_id, approved_account_ids) = self.internal_transfer(
+                        &account_id,
+                        &receiver_id,
+                        token_id,
+                        None,
+                        Some("guest removed".to_string()),
+                    );
+                    self.internal_refund_approved_account_ids(&previous_owner_id, &approved_account_ids);
+                }
+            }
+            _ => {
+                for token_id in &token_ids {
+                    self.internal_burn(&account_id, token_id);
+                }
+            }
+        }
+        if remaining > token_ids.len() {
+            return false;
+        }
+
+        // moving the tokens out leaves the guest an empty set
+        let initial_storage_usage = env::storage_usage();
+        self.tokens_per_owner.remove(&account_id);
+        self.guests.remove(&public_key);
+        let owner_id = self.owner_id.clone();
+        self.internal_release_storage(&owner_id, initial_storage_usage - env::storage_usage());
+
+        if let Some(balance_receiver_id) = balance_receiver_id.filter(|_| balance > 0) {
+            GuestEventKind::GuestWithdrawn(vec![GuestWithdrawLog {
+                account_id,
+                receiver_id: balance_receiver_id.clone(),
+                amount: guest.balance,
+                token_ids: vec![],
+            }])
+            .emit();
+            Promise::new(balance_receiver_id).transfer(balance);
+        }
+        true
     }
 
     /// view methods
@@ -280,12 +553,9 @@ impl Contract {
         self.guests.get(&public_key.into()).expect("no guest")
     }
 
-    pub fn get_account(&self, account_id: ValidAccountId) -> Vec<TokenId> {
-        self.tokens_per_owner.get(&account_id.into()).expect("no account").to_vec()
-    }
-
-    pub fn get_token_ids(&self) -> Vec<TokenId> {
-        self.token_ids.clone()
+    /// whether `add_guest` would refuse the account_id, used by the app to check usernames
+    pub fn is_account_registered(&self, account_id: ValidAccountId) -> bool {
+        self.tokens_per_owner.contains_key(account_id.as_ref())
     }
 
     /// self callbacks
@@ -299,22 +569,67 @@ impl Contract {
         success
     }
 
+    /// the withdrawal transfer failed, put the balance and the tokens back with the guest
+    pub fn on_guest_withdrawn(
+        &mut self,
+        public_key: PublicKey,
+        account_id: AccountId,
+        balance: U128,
+        token_ids: Vec<TokenId>,
+    ) -> bool {
+        assert_self();
+        let success = is_promise_success();
+        if success {
+            return success;
+        }
+        if let Some(mut guest) = self.guests.get(&public_key) {
+            guest.balance = U128(u128::from(guest.balance) + u128::from(balance));
+            self.guests.insert(&public_key, &guest);
+            for token_id in token_ids {
+                let owned = self
+                    .tokens_by_id
+                    .get(&token_id)
+                    .map(|token| token.owner_id == account_id)
+                    .unwrap_or(false);
+                if owned {
+                    self.internal_transfer(
+                        &account_id,
+                        &guest.account_id,
+                        &token_id,
+                        None,
+                        Some("guest withdrawal failed".to_string()),
+                    );
+                }
+            }
+        }
+        success
+    }
+
     /// remove approval and guest_sale if there was a removal or if market promise failed to add sale
     pub fn on_market_updated(&mut self, token_id: TokenId, market_contract: AccountId, sale: Option<GuestSale>) -> bool {
+        assert_self();
         let success = is_promise_success();
         if let Some(sale) = sale {
             if !success {
                 return success;
             }
             let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
-            token.approved_account_ids.insert(market_contract);
+            token.approved_account_ids.insert(market_contract, token.next_approval_id);
+            token.next_approval_id += 1;
             self.tokens_by_id.insert(&token_id, &token);
             self.guest_sales.insert(&token_id, &sale);
         } else {
             let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
             token.approved_account_ids.remove(&market_contract);
             self.tokens_by_id.insert(&token_id, &token);
-            self.guest_sales.remove(&token_id);
+            if let Some(guest_sale) = self.guest_sales.remove(&token_id) {
+                // the market returns the storage refund it sent along
+                if let PromiseResult::Successful(value) = env::promise_result(0) {
+                    if let Ok(refund) = near_sdk::serde_json::from_slice::<U128>(&value) {
+                        self.internal_credit_guest_sale(&token_id, &guest_sale, refund.into());
+                    }
+                }
+            }
         }
         success
     }
@@ -325,13 +640,13 @@ impl Contract {
 pub trait ExtContract {
     fn on_account_created(&mut self, public_key: PublicKey) -> bool;
     fn on_market_updated(&mut self, token_id: TokenId, market_contract: AccountId, sale: Option<GuestSale>) -> bool;
+    fn on_guest_withdrawn(&mut self, public_key: PublicKey, account_id: AccountId, balance: U128, token_ids: Vec<TokenId>) -> bool;
 }
 
 /// external calls to marketplace
 #[ext_contract(ext_market)]
 trait ExtTransfer {
-    fn add_sale(&mut self, token_contract_id: AccountId, token_id: String, price: U128, owner_id: AccountId, beneficiary: Option<AccountId>);
-    fn remove_sale(&mut self, token_contract_id: AccountId, token_id: String);
+    fn remove_sale_on_behalf(&mut self, token_id: String) -> U128;
 }
 
 fn is_promise_success() -> bool {
@@ -0,0 +1,92 @@
+use crate::*;
+use near_sdk::json_types::{Base64VecU8, U64};
+
+pub const NFT_METADATA_SPEC: &str = "nft-1.0.0";
+/// sha256 hashes are always 32 bytes once decoded from base64
+const HASH_LENGTH: usize = 32;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NFTContractMetadata {
+    pub spec: String,              // required, essentially a version like "nft-1.0.0"
+    pub name: String,              // required, ex. "Mosaics"
+    pub symbol: String,            // required, ex. "MOSAIC"
+    pub icon: Option<String>,      // Data URL
+    pub base_uri: Option<String>,  // Centralized gateway known to have reliable access to decentralized storage assets referenced by `reference` or `media` URLs
+    pub reference: Option<String>, // URL to a JSON file with more info
+    pub reference_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenMetadata {
+    pub title: Option<String>, // ex. "Arch Nemesis: Mail Carrier" or "Parcel #5055"
+    pub description: Option<String>, // free-form description
+    pub media: Option<String>, // URL to associated media, preferably to decentralized, content-addressed storage
+    pub media_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of content referenced by the `media` field. Optional here, see `assert_valid`.
+    pub copies: Option<U64>, // number of copies of this set of metadata in existence when token was minted.
+    pub issued_at: Option<String>, // ISO 8601 datetime when token was issued or minted
+    pub expires_at: Option<String>, // ISO 8601 datetime when token expires
+    pub starts_at: Option<String>, // ISO 8601 datetime when token starts being valid
+    pub updated_at: Option<String>, // ISO 8601 datetime when token was last updated
+    pub extra: Option<String>, // anything extra the NFT wants to store on-chain. Can be stringified JSON.
+    pub reference: Option<String>, // URL to an off-chain JSON file with more info.
+    pub reference_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
+}
+
+fn assert_hash(hash: &Option<Base64VecU8>, name: &str) {
+    if let Some(hash) = hash {
+        assert_eq!(
+            hash.0.len(),
+            HASH_LENGTH,
+            "{} must be a base64 encoded sha256 hash of {} bytes",
+            name,
+            HASH_LENGTH
+        );
+    }
+}
+
+fn assert_reference(reference: &Option<String>, reference_hash: &Option<Base64VecU8>) {
+    assert_eq!(
+        reference.is_some(),
+        reference_hash.is_some(),
+        "reference and reference_hash must be provided together"
+    );
+    assert_hash(reference_hash, "reference_hash");
+}
+
+impl NFTContractMetadata {
+    pub fn assert_valid(&self) {
+        assert_eq!(self.spec.as_str(), NFT_METADATA_SPEC, "Spec must be {}", NFT_METADATA_SPEC);
+        assert!(!self.name.is_empty(), "Name is required");
+        assert!(!self.symbol.is_empty(), "Symbol is required");
+        assert_reference(&self.reference, &self.reference_hash);
+    }
+}
+
+impl TokenMetadata {
+    /// media_hash is only checked one way: guests mint from a pasted image URL,
+    /// which the app often can't fetch (CORS) to hash, so `media` alone is accepted
+    pub fn assert_valid(&self) {
+        assert!(
+            self.media_hash.is_none() || self.media.is_some(),
+            "media_hash requires media"
+        );
+        assert_hash(&self.media_hash, "media_hash");
+        assert_reference(&self.reference, &self.reference_hash);
+        if let Some(copies) = &self.copies {
+            assert!(copies.0 > 0, "Copies must be greater than 0");
+        }
+    }
+}
+
+pub trait NonFungibleTokenMetadata {
+    fn nft_metadata(&self) -> NFTContractMetadata;
+}
+
+#[near_bindgen]
+impl NonFungibleTokenMetadata for Contract {
+    fn nft_metadata(&self) -> NFTContractMetadata {
+        self.metadata.get().unwrap()
+    }
+}
@@ -1,28 +1,70 @@
 use crate::*;
 
+/// who can call `nft_mint`, guests mint through `nft_mint_guest` either way
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum MintingPolicy {
+    Open,
+    OwnerOnly,
+}
+
 #[near_bindgen]
 impl Contract {
     #[payable]
-    pub fn nft_mint(&mut self, token_id: TokenId, metadata: String) {
+    pub fn nft_mint(
+        &mut self,
+        token_id: TokenId,
+        metadata: TokenMetadata,
+        perpetual_royalties: Option<Royalty>,
+    ) {
         let initial_storage_usage = env::storage_usage();
-        // self.assert_owner();
-        let token = Token {
+        metadata.assert_valid();
+        if self.minting_policy == MintingPolicy::OwnerOnly {
+            self.assert_owner();
+        }
+        let royalty = perpetual_royalties.unwrap_or_else(|| self.default_royalty.clone());
+        assert_valid_royalty(&royalty);
+        let mut token = Token {
             owner_id: env::predecessor_account_id(),
             metadata,
             approved_account_ids: Default::default(),
-            approval_id: 0,
+            next_approval_id: 0,
+            royalty,
+            storage_payer_id: env::predecessor_account_id(),
+            storage_used: 0,
         };
         assert!(
             self.tokens_by_id.insert(&token_id, &token).is_none(),
             "Token already exists"
         );
-        self.internal_add_token_to_owner(&token.owner_id, &token_id);
+        let set_storage_usage = self.internal_add_token_to_owner(&token.owner_id, &token_id);
         self.total_supply += 1;
 
-        let new_token_size_in_bytes = env::storage_usage() - initial_storage_usage;
-        let required_storage_in_bytes =
-            self.extra_storage_in_bytes_per_token + new_token_size_in_bytes;
+        let new_token_size_in_bytes = env::storage_usage() - initial_storage_usage - set_storage_usage;
+        // remembered so burning the token credits back exactly what was charged,
+        // the owner's token set is credited back when it empties
+        token.storage_used = new_token_size_in_bytes;
+        self.tokens_by_id.insert(&token_id, &token);
+
+        let refund = self.internal_charge_storage(
+            &token.storage_payer_id,
+            new_token_size_in_bytes + set_storage_usage,
+            env::attached_deposit(),
+        );
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        NftEventKind::NftMint(vec![NftMintLog {
+            owner_id: token.owner_id,
+            token_ids: vec![token_id],
+            memo: None,
+        }])
+        .emit();
+    }
 
-        deposit_refund(required_storage_in_bytes);
+    pub fn get_minting_policy(&self) -> MintingPolicy {
+        self.minting_policy.clone()
     }
 }
@@ -31,9 +31,16 @@ pub trait NonFungibleTokenCore {
 
     fn nft_revoke_all(&mut self, token_id: TokenId) -> bool;
 
+    fn nft_is_approved(
+        &self,
+        token_id: TokenId,
+        approved_account_id: ValidAccountId,
+        approval_id: Option<u64>,
+    ) -> bool;
+
     fn nft_total_supply(&self) -> U64;
 
-    fn nft_token(&self, token_id: TokenId) -> Option<Token>;
+    fn nft_token(&self, token_id: TokenId) -> Option<JsonToken>;
 }
 
 #[ext_contract(ext_non_fungible_token_receiver)]
@@ -67,7 +74,7 @@ trait NonFungibleTokenResolver {
         &mut self,
         owner_id: AccountId,
         receiver_id: AccountId,
-        approved_account_ids: HashSet<AccountId>,
+        approved_account_ids: HashMap<AccountId, u64>,
         token_id: TokenId,
     ) -> bool;
 }
@@ -77,7 +84,7 @@ trait NonFungibleTokenResolver {
         &mut self,
         owner_id: AccountId,
         receiver_id: AccountId,
-        approved_account_ids: HashSet<AccountId>,
+        approved_account_ids: HashMap<AccountId, u64>,
         token_id: TokenId,
     ) -> bool;
 }
@@ -105,14 +112,10 @@ impl NonFungibleTokenCore for Contract {
 
         // If sale was made on_behalf_of guest then give guest the balance of the sale
         if let Some(guest_sale) = self.guest_sales.get(&token_id) {
-            let mut guest = self.guests.get(&guest_sale.public_key).expect("No guest");
-            let new_balance = u128::from(guest.balance) + guest_sale.price;
-            guest.balance = U128(new_balance);
-            env::log(format!("New guest balance {}", new_balance).as_bytes());
-            self.guests.insert(&guest_sale.public_key, &guest);
+            self.internal_credit_guest_sale(&token_id, &guest_sale, guest_sale.price);
         }
 
-        refund_approved_account_ids(previous_owner_id, &approved_account_ids);
+        self.internal_refund_approved_account_ids(&previous_owner_id, &approved_account_ids);
     }
 
     #[payable]
@@ -161,34 +164,42 @@ impl NonFungibleTokenCore for Contract {
         account_id: ValidAccountId,
         msg: Option<String>,
     ) -> bool {
-        let mut deposit = env::attached_deposit();
+        assert_at_least_one_yocto();
         let account_id: AccountId = account_id.into();
-        let storage_required = bytes_for_approved_account_id(&account_id);
-        assert!(deposit >= storage_required as u128, "Deposit doesn't cover storage of account_id: {}", account_id.clone());
-
         let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
         assert_eq!(&env::predecessor_account_id(), &token.owner_id);
 
-        if token.approved_account_ids.insert(account_id.clone()) {
-            deposit -= storage_required as u128;
+        // approving an already approved account gives it a new approval id
+        let approval_id = token.next_approval_id;
+        let is_new_approval = token
+            .approved_account_ids
+            .insert(account_id.clone(), approval_id)
+            .is_none();
+        token.next_approval_id += 1;
 
-            token.approval_id += 1;
-
-            self.tokens_by_id.insert(&token_id, &token);
-            ext_non_fungible_approval_receiver::nft_on_approve(
-                env::current_account_id(),
-                token_id,
-                token.owner_id,
-                token.approval_id,
-                msg,
-                &account_id,
-                deposit,
-                env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL,
-            );
-            true
+        let initial_storage_usage = env::storage_usage();
+        self.tokens_by_id.insert(&token_id, &token);
+        let deposit = if is_new_approval {
+            // approval storage comes out of the owner's storage balance, the rest is for the approved account
+            self.internal_charge_storage(
+                &token.owner_id,
+                env::storage_usage() - initial_storage_usage,
+                env::attached_deposit(),
+            )
         } else {
-            false
-        }
+            env::attached_deposit()
+        };
+        ext_non_fungible_approval_receiver::nft_on_approve(
+            env::current_account_id(),
+            token_id,
+            token.owner_id,
+            approval_id,
+            msg,
+            &account_id,
+            deposit,
+            env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL,
+        );
+        is_new_approval
     }
 
     #[payable]
@@ -201,11 +212,13 @@ impl NonFungibleTokenCore for Contract {
         let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
         let predecessor_account_id = env::predecessor_account_id();
         assert_eq!(&predecessor_account_id, &token.owner_id);
-        if token.approved_account_ids.remove(account_id.as_ref()) {
-            let storage_released = bytes_for_approved_account_id(account_id.as_ref());
-            Promise::new(env::predecessor_account_id())
-                .transfer(Balance::from(storage_released) * STORAGE_PRICE_PER_BYTE);
+        if token.approved_account_ids.remove(account_id.as_ref()).is_some() {
+            let initial_storage_usage = env::storage_usage();
             self.tokens_by_id.insert(&token_id, &token);
+            self.internal_release_storage(
+                &predecessor_account_id,
+                initial_storage_usage - env::storage_usage(),
+            );
             true
         } else {
             false
@@ -222,21 +235,42 @@ impl NonFungibleTokenCore for Contract {
         let predecessor_account_id = env::predecessor_account_id();
         assert_eq!(&predecessor_account_id, &token.owner_id);
         if !token.approved_account_ids.is_empty() {
-            refund_approved_account_ids(predecessor_account_id, &token.approved_account_ids);
             token.approved_account_ids.clear();
+            let initial_storage_usage = env::storage_usage();
             self.tokens_by_id.insert(&token_id, &token);
+            self.internal_release_storage(
+                &predecessor_account_id,
+                initial_storage_usage - env::storage_usage(),
+            );
             true
         } else {
             false
         }
     }
 
+    fn nft_is_approved(
+        &self,
+        token_id: TokenId,
+        approved_account_id: ValidAccountId,
+        approval_id: Option<u64>,
+    ) -> bool {
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        match token.approved_account_ids.get(approved_account_id.as_ref()) {
+            Some(actual_approval_id) => approval_id
+                .map(|approval_id| &approval_id == actual_approval_id)
+                .unwrap_or(true),
+            None => false,
+        }
+    }
+
     fn nft_total_supply(&self) -> U64 {
         self.total_supply.into()
     }
 
-    fn nft_token(&self, token_id: TokenId) -> Option<Token> {
-        self.tokens_by_id.get(&token_id)
+    fn nft_token(&self, token_id: TokenId) -> Option<JsonToken> {
+        self.tokens_by_id
+            .get(&token_id)
+            .map(|token| JsonToken::new(token_id, token))
     }
 }
 
@@ -246,7 +280,7 @@ impl NonFungibleTokenResolver for Contract {
         &mut self,
         owner_id: AccountId,
         receiver_id: AccountId,
-        approved_account_ids: HashSet<AccountId>,
+        approved_account_ids: HashMap<AccountId, u64>,
         token_id: TokenId,
     ) -> bool {
         assert_self();
@@ -257,7 +291,7 @@ impl NonFungibleTokenResolver for Contract {
             if let Ok(return_token) = near_sdk::serde_json::from_slice::<bool>(&value) {
                 if !return_token {
                     // Token was successfully received.
-                    refund_approved_account_ids(owner_id, &approved_account_ids);
+                    self.internal_refund_approved_account_ids(&owner_id, &approved_account_ids);
                     return true;
                 }
             }
@@ -266,25 +300,37 @@ impl NonFungibleTokenResolver for Contract {
         let mut token = if let Some(token) = self.tokens_by_id.get(&token_id) {
             if &token.owner_id != &receiver_id {
                 // The token is not owner by the receiver anymore. Can't return it.
-                refund_approved_account_ids(owner_id, &approved_account_ids);
+                self.internal_refund_approved_account_ids(&owner_id, &approved_account_ids);
                 return true;
             }
             token
         } else {
             // The token was burned and doesn't exist anymore.
-            refund_approved_account_ids(owner_id, &approved_account_ids);
+            self.internal_refund_approved_account_ids(&owner_id, &approved_account_ids);
             return true;
         };
 
-        env::log(format!("Return {} from @{} to @{}", token_id, receiver_id, owner_id).as_bytes());
-
         self.internal_remove_token_from_owner(&receiver_id, &token_id);
-        self.internal_add_token_to_owner(&owner_id, &token_id);
+        // the owner was credited its token set when the token left, the resolve can't fail
+        // so an owner that withdrew that balance in the meantime owes it instead
+        let set_storage_usage = self.internal_add_token_to_owner(&owner_id, &token_id);
+        if set_storage_usage > 0 {
+            self.internal_owe_storage(&owner_id, set_storage_usage);
+        }
         token.owner_id = owner_id;
-        refund_approved_account_ids(receiver_id, &token.approved_account_ids);
+        self.internal_refund_approved_account_ids(&receiver_id, &token.approved_account_ids);
         token.approved_account_ids = approved_account_ids;
         self.tokens_by_id.insert(&token_id, &token);
 
+        NftEventKind::NftTransfer(vec![NftTransferLog {
+            authorized_id: None,
+            old_owner_id: receiver_id,
+            new_owner_id: token.owner_id,
+            token_ids: vec![token_id],
+            memo: None,
+        }])
+        .emit();
+
         false
     }
 }
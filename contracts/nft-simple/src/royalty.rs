@@ -0,0 +1,126 @@
+use crate::*;
+
+/// royalties are expressed in basis points, 10_000 being the whole balance
+pub(crate) const ROYALTY_TOTAL_BPS: u32 = 10_000;
+/// creators can't take more than half of a sale in perpetuity
+const MAX_ROYALTY_BPS: u32 = 5_000;
+/// bounds the payout map (and the gas a market spends distributing it)
+const MAX_ROYALTY_RECIPIENTS: usize = 10;
+
+pub type Royalty = HashMap<AccountId, u32>;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Payout {
+    pub payout: HashMap<AccountId, U128>,
+}
+
+pub trait NonFungibleTokenPayout {
+    fn nft_payout(&self, token_id: TokenId, balance: U128, max_len_payout: u32) -> Payout;
+
+    fn nft_transfer_payout(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        balance: U128,
+        max_len_payout: u32,
+    ) -> Payout;
+}
+
+pub(crate) fn assert_valid_royalty(royalty: &Royalty) {
+    assert!(
+        royalty.len() <= MAX_ROYALTY_RECIPIENTS,
+        "Cannot add more than {} royalty recipients",
+        MAX_ROYALTY_RECIPIENTS
+    );
+    let mut total = 0;
+    for (account_id, bps) in royalty.iter() {
+        assert!(
+            env::is_valid_account_id(account_id.as_bytes()),
+            "Invalid royalty account {}",
+            account_id
+        );
+        total += bps;
+    }
+    assert!(
+        total <= MAX_ROYALTY_BPS,
+        "Royalties cannot exceed {} basis points",
+        MAX_ROYALTY_BPS
+    );
+}
+
+fn royalty_to_payout(bps: u32, balance: Balance) -> U128 {
+    U128(Balance::from(bps) * balance / Balance::from(ROYALTY_TOTAL_BPS))
+}
+
+/// splits balance between the royalty recipients, the rest goes to owner_id
+pub(crate) fn compute_payout(
+    royalty: &Royalty,
+    owner_id: &AccountId,
+    balance: Balance,
+    max_len_payout: u32,
+) -> Payout {
+    assert!(
+        royalty.len() as u32 + 1 <= max_len_payout,
+        "Market cannot payout to that many receivers"
+    );
+    let mut payout = HashMap::new();
+    let mut total_perpetual = 0;
+    for (account_id, bps) in royalty.iter() {
+        if account_id != owner_id {
+            payout.insert(account_id.clone(), royalty_to_payout(*bps, balance));
+            total_perpetual += bps;
+        }
+    }
+    payout.insert(
+        owner_id.clone(),
+        royalty_to_payout(ROYALTY_TOTAL_BPS - total_perpetual, balance),
+    );
+    Payout { payout }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenPayout for Contract {
+    fn nft_payout(&self, token_id: TokenId, balance: U128, max_len_payout: u32) -> Payout {
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        compute_payout(&token.royalty, &token.owner_id, balance.into(), max_len_payout)
+    }
+
+    #[payable]
+    fn nft_transfer_payout(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        balance: U128,
+        max_len_payout: u32,
+    ) -> Payout {
+        assert_one_yocto();
+        let royalty = self.tokens_by_id.get(&token_id).expect("Token not found").royalty;
+
+        let sender_id = env::predecessor_account_id();
+        let (previous_owner_id, approved_account_ids) = self.internal_transfer(
+            &sender_id,
+            receiver_id.as_ref(),
+            &token_id,
+            approval_id,
+            memo,
+        );
+        self.internal_refund_approved_account_ids(&previous_owner_id, &approved_account_ids);
+
+        let mut payout = compute_payout(&royalty, &previous_owner_id, balance.into(), max_len_payout);
+        // guest proceeds are escrowed by this contract, so the owner's share is paid here
+        // on top of any royalty this contract is owed, and only that share is credited to the guest
+        if let Some(guest_sale) = self.guest_sales.get(&token_id) {
+            let owner_share = payout.payout.remove(&previous_owner_id).unwrap().0;
+            self.internal_credit_guest_sale(&token_id, &guest_sale, owner_share);
+            let current_account_id = env::current_account_id();
+            let royalty_share = payout.payout.get(&current_account_id).map(|amount| amount.0).unwrap_or(0);
+            payout.payout.insert(current_account_id, U128(royalty_share + owner_share));
+        }
+        payout
+    }
+}
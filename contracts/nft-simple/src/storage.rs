@@ -0,0 +1,235 @@
+use crate::*;
+
+/// per-account ledger, `used` includes the bytes of the ledger entry itself
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountStorage {
+    pub total: Balance,
+    pub used: StorageUsage,
+}
+
+impl AccountStorage {
+    /// nothing is available while the account owes storage, see `internal_owe_storage`
+    fn available(&self) -> Balance {
+        self.total.saturating_sub(Balance::from(self.used) * env::storage_byte_cost())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+impl From<&AccountStorage> for StorageBalance {
+    fn from(account: &AccountStorage) -> Self {
+        Self {
+            total: account.total.into(),
+            available: account.available().into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+pub trait StorageManagement {
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance;
+
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance;
+
+    /// accounts can only unregister once all their tokens and approvals are gone, `force` is not supported
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool;
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds;
+
+    fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance>;
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id
+            .map(|a| a.into())
+            .unwrap_or_else(env::predecessor_account_id);
+        let min_balance = self.storage_balance_min();
+
+        let account = if let Some(mut account) = self.storage_accounts.get(&account_id) {
+            if registration_only.unwrap_or(false) {
+                if amount > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(amount);
+                }
+            } else {
+                account.total += amount;
+                self.storage_accounts.insert(&account_id, &account);
+            }
+            account
+        } else {
+            assert!(
+                amount >= min_balance,
+                "The attached deposit is less than the minimum storage balance {}",
+                min_balance
+            );
+            let mut account = self.internal_new_account_storage();
+            if registration_only.unwrap_or(false) {
+                account.total = min_balance;
+                let refund = amount - min_balance;
+                if refund > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(refund);
+                }
+            } else {
+                account.total = amount;
+            }
+            self.storage_accounts.insert(&account_id, &account);
+            account
+        };
+        (&account).into()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut account = self
+            .storage_accounts
+            .get(&account_id)
+            .expect("The account is not registered");
+        let available = account.available();
+        let amount = amount.map(|a| a.into()).unwrap_or(available);
+        assert!(
+            amount <= available,
+            "The amount is greater than the available storage balance"
+        );
+        account.total -= amount;
+        self.storage_accounts.insert(&account_id, &account);
+        if amount > 0 {
+            Promise::new(account_id).transfer(amount);
+        }
+        (&account).into()
+    }
+
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        assert!(!force.unwrap_or(false), "Force unregister is not supported");
+        let account_id = env::predecessor_account_id();
+        if let Some(account) = self.storage_accounts.get(&account_id) {
+            assert_eq!(
+                account.used, self.account_storage_usage,
+                "Can't unregister the account while it still uses storage"
+            );
+            self.storage_accounts.remove(&account_id);
+            Promise::new(account_id).transfer(account.total);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: self.storage_balance_min().into(),
+            max: None,
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.storage_accounts
+            .get(account_id.as_ref())
+            .map(|account| (&account).into())
+    }
+}
+
+impl Contract {
+    pub(crate) fn measure_account_storage_usage(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id = "a".repeat(64);
+        self.storage_accounts.insert(
+            &tmp_account_id,
+            &AccountStorage { total: 0, used: 0 },
+        );
+        self.account_storage_usage = env::storage_usage() - initial_storage_usage;
+        self.storage_accounts.remove(&tmp_account_id);
+    }
+
+    fn storage_balance_min(&self) -> Balance {
+        Balance::from(self.account_storage_usage) * env::storage_byte_cost()
+    }
+
+    fn internal_new_account_storage(&self) -> AccountStorage {
+        AccountStorage {
+            total: 0,
+            used: self.account_storage_usage,
+        }
+    }
+
+    /// Debits `storage_used` bytes from the account's storage balance, registering it if needed.
+    /// Any shortfall is taken from `deposit` and whatever is left of `deposit` is returned.
+    pub(crate) fn internal_charge_storage(
+        &mut self,
+        account_id: &AccountId,
+        storage_used: StorageUsage,
+        deposit: Balance,
+    ) -> Balance {
+        let mut account = self
+            .storage_accounts
+            .get(account_id)
+            .unwrap_or_else(|| self.internal_new_account_storage());
+        account.used += storage_used;
+        let required = Balance::from(account.used) * env::storage_byte_cost();
+        let mut remaining = deposit;
+        if required > account.total {
+            let shortfall = required - account.total;
+            assert!(
+                deposit >= shortfall,
+                "Requires {} more yoctoNEAR in storage balance or attached deposit",
+                shortfall
+            );
+            account.total += shortfall;
+            remaining -= shortfall;
+        }
+        self.storage_accounts.insert(account_id, &account);
+        remaining
+    }
+
+    /// Debits `storage_used` bytes like `internal_charge_storage` but never panics, for callbacks that can't fail.
+    /// A shortfall stays as a debt that the next charge or deposit has to cover first.
+    pub(crate) fn internal_owe_storage(&mut self, account_id: &AccountId, storage_used: StorageUsage) {
+        let mut account = self
+            .storage_accounts
+            .get(account_id)
+            .unwrap_or_else(|| self.internal_new_account_storage());
+        account.used += storage_used;
+        self.storage_accounts.insert(account_id, &account);
+    }
+
+    /// Credits `storage_released` bytes back to the account's storage balance.
+    /// Storage paid by this contract itself (guests) isn't tracked.
+    pub(crate) fn internal_release_storage(
+        &mut self,
+        account_id: &AccountId,
+        storage_released: StorageUsage,
+    ) {
+        if let Some(mut account) = self.storage_accounts.get(account_id) {
+            account.used = std::cmp::max(
+                account.used.saturating_sub(storage_released),
+                self.account_storage_usage,
+            );
+            self.storage_accounts.insert(account_id, &account);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+#!/bin/bash
+set -e
+
+# NEP-141 token the app tests buy market sales with
+RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release
+mkdir -p ../../out
+cp target/wasm32-unknown-unknown/release/*.wasm ../../out/test_ft.wasm
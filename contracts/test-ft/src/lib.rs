@@ -0,0 +1,140 @@
+//! Bare NEP-141 token for the app tests, registration is free and there is no metadata
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::{U128, ValidAccountId};
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, Balance, Gas, PanicOnDefault, PromiseOrValue, PromiseResult};
+
+#[global_allocator]
+static ALLOC: near_sdk::wee_alloc::WeeAlloc<'_> = near_sdk::wee_alloc::WeeAlloc::INIT;
+
+const GAS_FOR_RESOLVE_TRANSFER: Gas = 10_000_000_000_000;
+const GAS_FOR_FT_TRANSFER_CALL: Gas = 25_000_000_000_000 + GAS_FOR_RESOLVE_TRANSFER;
+const NO_DEPOSIT: Balance = 0;
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Contract {
+    pub accounts: LookupMap<AccountId, Balance>,
+    pub total_supply: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(owner_id: ValidAccountId, total_supply: U128) -> Self {
+        assert!(!env::state_exists(), "Already initialized");
+        let mut this = Self {
+            accounts: LookupMap::new(b"a".to_vec()),
+            total_supply: total_supply.into(),
+        };
+        this.accounts.insert(owner_id.as_ref(), &total_supply.into());
+        this
+    }
+
+    /// any attached deposit is kept, accounts must be registered to receive tokens
+    #[payable]
+    pub fn storage_deposit(&mut self, account_id: Option<ValidAccountId>) {
+        let account_id = account_id
+            .map(|a| a.into())
+            .unwrap_or_else(env::predecessor_account_id);
+        if self.accounts.get(&account_id).is_none() {
+            self.accounts.insert(&account_id, &0);
+        }
+    }
+
+    #[payable]
+    pub fn ft_transfer(&mut self, receiver_id: ValidAccountId, amount: U128, memo: Option<String>) {
+        assert_eq!(env::attached_deposit(), 1, "Requires attached deposit of exactly 1 yoctoNEAR");
+        self.internal_transfer(&env::predecessor_account_id(), receiver_id.as_ref(), amount.into(), memo);
+    }
+
+    #[payable]
+    pub fn ft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        assert_eq!(env::attached_deposit(), 1, "Requires attached deposit of exactly 1 yoctoNEAR");
+        let sender_id = env::predecessor_account_id();
+        self.internal_transfer(&sender_id, receiver_id.as_ref(), amount.into(), memo);
+        ext_ft_receiver::ft_on_transfer(
+            sender_id.clone(),
+            amount,
+            msg,
+            receiver_id.as_ref(),
+            NO_DEPOSIT,
+            env::prepaid_gas() - GAS_FOR_FT_TRANSFER_CALL,
+        )
+        .then(ext_self::ft_resolve_transfer(
+            sender_id,
+            receiver_id.into(),
+            amount,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+        .into()
+    }
+
+    /// returns the used amount, the unused part goes back to the sender
+    pub fn ft_resolve_transfer(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) -> U128 {
+        assert_eq!(env::predecessor_account_id(), env::current_account_id(), "Method is private");
+        let amount: Balance = amount.into();
+        let unused = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<U128>(&value)
+                .map(|unused| std::cmp::min(amount, unused.0))
+                .unwrap_or(amount),
+            _ => amount,
+        };
+        if unused > 0 {
+            let receiver_balance = self.accounts.get(&receiver_id).unwrap_or(0);
+            let refund = std::cmp::min(unused, receiver_balance);
+            self.accounts.insert(&receiver_id, &(receiver_balance - refund));
+            let sender_balance = self.accounts.get(&sender_id).unwrap_or(0);
+            self.accounts.insert(&sender_id, &(sender_balance + refund));
+            return (amount - refund).into();
+        }
+        amount.into()
+    }
+
+    /// view methods
+
+    pub fn ft_balance_of(&self, account_id: ValidAccountId) -> U128 {
+        self.accounts.get(account_id.as_ref()).unwrap_or(0).into()
+    }
+
+    pub fn ft_total_supply(&self) -> U128 {
+        self.total_supply.into()
+    }
+}
+
+impl Contract {
+    fn internal_transfer(&mut self, sender_id: &AccountId, receiver_id: &AccountId, amount: Balance, memo: Option<String>) {
+        assert_ne!(sender_id, receiver_id, "Sender and receiver should be different");
+        assert!(amount > 0, "The amount should be a positive number");
+        let sender_balance = self.accounts.get(sender_id).expect("The sender is not registered");
+        assert!(sender_balance >= amount, "The sender does not have enough balance");
+        let receiver_balance = self
+            .accounts
+            .get(receiver_id)
+            .unwrap_or_else(|| env::panic(format!("The account {} is not registered", receiver_id).as_bytes()));
+        self.accounts.insert(sender_id, &(sender_balance - amount));
+        self.accounts.insert(receiver_id, &(receiver_balance + amount));
+        if let Some(memo) = memo {
+            env::log(format!("Memo: {}", memo).as_bytes());
+        }
+    }
+}
+
+#[ext_contract(ext_ft_receiver)]
+trait FungibleTokenReceiver {
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128>;
+}
+
+#[ext_contract(ext_self)]
+trait FungibleTokenResolver {
+    fn ft_resolve_transfer(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) -> U128;
+}
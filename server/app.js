@@ -39,7 +39,8 @@ app.post('/add-guest', async (req, res) => {
     console.log('\nAdding guest account:', account_id)
 	try {
 		const addKey = await guestsAccount.addKey(public_key, contractId, contractMethods.changeMethods, parseNearAmount('0.1'));
-		const add_guest = await contractAccount.functionCall(contractId, 'add_guest', { account_id, public_key }, GAS);
+		/// guest record storage is paid by the contract owner, unused deposit is refunded
+		const add_guest = await contractAccount.functionCall(contractId, 'add_guest', { account_id, public_key }, GAS, parseNearAmount('0.01'));
 		res.json({ success: true, result: { addKey, add_guest } });
 	} catch(e) {
 		console.log(e);
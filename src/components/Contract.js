@@ -46,7 +46,7 @@ export const Contract = ({ near, update, localKeys = {}, account }) => {
 		const contract = getContract(appAccount);
 		await contract[!account ? 'nft_mint_guest' : 'nft_mint']({
             token_id: 'token-' + Date.now(),
-			metadata,
+			metadata: { media: metadata },
 		}, GAS, deposit);
 		checkFreebies();
 		update('loading', false);
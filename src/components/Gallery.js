@@ -1,6 +1,5 @@
 import React, {useEffect, useState} from 'react';
 import * as nearAPI from 'near-api-js';
-import { get, set, del } from '../utils/storage';
 import { GAS, parseNearAmount } from '../state/near';
 import { 
     marketId,
@@ -8,11 +7,8 @@ import {
 	getContract,
 	formatAccountId,
     createGuestAccount,
-    marketDeposit,
 } from '../utils/near-utils';
 
-const ADD_SALE = '__ADD_SALE';
-
 const {
 	KeyPair,
 	utils: { format: { formatNearAmount } }
@@ -33,37 +29,35 @@ export const Gallery = ({ near, signedIn, contractAccount, account, localKeys, l
 	const loadItems = async () => {
 		setFetching(true);
 		const contract = getContract(contractAccount);
-		const token_ids = await contract.get_token_ids();
-        console.log(token_ids)
-		const newItems = [];
-		for (let i = 0; i < token_ids.length; i++) {
-            const token_id = token_ids[i]
-			const data = await contract.nft_token({
-				token_id
+		const tokens = [];
+		const limit = 50;
+		let page = [];
+		do {
+			page = await contract.nft_tokens({ from_index: tokens.length.toString(), limit });
+			tokens.push(...page);
+		} while (page.length === limit);
+		/// all of this contract's sales from the marketplace contract
+		const sales = {};
+		let salesCount = 0;
+		do {
+			page = await contractAccount.viewFunction(marketId, 'get_sales_by_nft_contract_id', {
+				nft_contract_id: contractAccount.accountId,
+				from_index: salesCount.toString(),
+				limit
 			});
+			salesCount += page.length;
+			page.forEach((sale) => sales[sale.token_id] = sale);
+		} while (page.length === limit);
+		const newItems = [];
+		for (let i = 0; i < tokens.length; i++) {
+			const data = tokens[i];
+			const { token_id } = data;
             data.sales = []
-            for (let j = 0; j < data.approved_account_ids.length; j++) {
-                const marketId = data.approved_account_ids[j]
-                /// gotta get sale from marketplace contract
-                try {
-                    const sale = await contractAccount.viewFunction(marketId, 'get_sale', {
-                        token_contract_id: contractAccount.accountId,
-                        token_id
-                    });
-                    data.sales.push(sale)
-                } catch (e) {
-                    console.warn(e)
-                    const sale = get(ADD_SALE, {})
-                    if (sale.price) {
-                        del(ADD_SALE)
-                        await account.functionCall(marketId, 'add_sale', sale, GAS, marketDeposit)
-                    }
-                }
+            /// sales are added by the market when the token is approved with a price
+            if (sales[token_id]) {
+                data.sales.push(sales[token_id])
             }
-			newItems.push({
-				...data,
-				token_id
-			});
+			newItems.push(data);
 		}
 		newItems.reverse();
 		setItems(newItems);
@@ -76,6 +70,10 @@ export const Gallery = ({ near, signedIn, contractAccount, account, localKeys, l
 		update('loading', true);
 		const item = items.find(({ token_id: id }) => token_id === id);
         if (!item.sales.length) return
+		/// buyers pay for their token set on the nft contract, register a storage balance once
+		if (!await account.viewFunction(contractName, 'storage_balance_of', { account_id: account.accountId })) {
+			await account.functionCall(contractName, 'storage_deposit', {}, GAS, parseNearAmount('0.01'));
+		}
 		await account.functionCall(marketId, 'purchase', {
             token_contract_id: contractName,
 			token_id: token_id,
@@ -84,6 +82,13 @@ export const Gallery = ({ near, signedIn, contractAccount, account, localKeys, l
 		update('loading', false);
 	};
 
+	/// top up the seller's market storage balance only when it can't cover another listing
+	const getMarketDeposit = async (accountId) => {
+		const { min } = await contractAccount.viewFunction(marketId, 'storage_balance_bounds', {});
+		const balance = await contractAccount.viewFunction(marketId, 'storage_balance_of', { account_id: accountId });
+		return balance && BigInt(balance.available) >= BigInt(min) ? '0' : min;
+	};
+
 	const handleSetPrice = async (token_id) => {
 		update('loading', true);
 		if (!account) {
@@ -100,13 +105,12 @@ export const Gallery = ({ near, signedIn, contractAccount, account, localKeys, l
                 token_id,
                 price: parseNearAmount(amount),
                 market_id: marketId,
-                market_deposit: marketDeposit
+                market_deposit: await getMarketDeposit(localKeys.accessAccountId)
             }, GAS)
 		} else {
 			try {
 				const sale = await account.viewFunction(marketId, 'get_sale', { token_contract_id: contractName, token_id });
 				console.log('\n\nSale exists, updating price:', sale, '\n\n');
-				/// paying above marketDeposit, but after sale deposit will be paid back
 				await account.functionCall(marketId, 'update_price', {
 					token_contract_id: contractName,
 					token_id,
@@ -114,7 +118,7 @@ export const Gallery = ({ near, signedIn, contractAccount, account, localKeys, l
 				}, GAS)
 			} catch(e) {
 				console.warn(e)
-				/// paying above marketDeposit, but after sale deposit will be paid back
+				/// anything not needed for the approval is added to the market storage balance
 				await account.functionCall(contractName, 'nft_approve_account_id', {
 					token_id,
 					account_id: marketId,
@@ -150,7 +154,7 @@ export const Gallery = ({ near, signedIn, contractAccount, account, localKeys, l
 		</div>}
 		{
 			(filter === 1 ? market : mine).map(({ metadata, owner_id, sales, token_id }) => <div key={token_id} className="item">
-				<img src={metadata} />
+				<img src={metadata.media} />
 				{(filter === 1) &&<div className="line"></div>}
 				{filter === 1 && <p>Owned by {formatAccountId(owner_id)}</p>}
 				{
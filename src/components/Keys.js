@@ -56,11 +56,8 @@ export const Keys = ({ near, update, localKeys }) => {
 		}
         const account_id = username + '.' + contractName
         const contractAccount = new Account(near.connection, contractName)
-        try {
-            await contractAccount.viewFunction(contractName, 'get_account', { account_id })
+        if (await contractAccount.viewFunction(contractName, 'is_account_registered', { account_id })) {
             return alert('username taken')
-        } catch (e) {
-            console.warn(e)
         }
 		update('loading', true);
 		const { seedPhrase, publicKey, secretKey } = generateSeedPhrase();
@@ -75,7 +72,7 @@ export const Keys = ({ near, update, localKeys }) => {
 		});
 		if (result && result.success) {
 			try {
-                await contractAccount.viewFunction(contractName, 'get_account', { account_id })
+                await contractAccount.viewFunction(contractName, 'get_guest', { public_key })
                 const keys = {
 					seedPhrase,
                     accessAccountId: account_id,
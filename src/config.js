@@ -22,15 +22,36 @@ module.exports = function getConfig() {
           "new",
           "nft_mint",
           "nft_transfer",
+          "nft_transfer_payout",
+          "nft_burn",
+          "storage_deposit",
+          "storage_withdraw",
+          "storage_unregister",
           "add_guest",
           "remove_guest",
           "nft_approve_account_id",
           "nft_mint_guest",
           "nft_add_sale_guest",
           "nft_remove_sale_guest",
+          "nft_transfer_guest",
           "upgrade_guest",
+          "withdraw_guest_to",
+        ],
+        viewMethods: [
+          "get_guest",
+          "is_account_registered",
+          "nft_token",
+          "nft_total_supply",
+          "nft_tokens",
+          "nft_supply_for_owner",
+          "nft_tokens_for_owner",
+          "nft_metadata",
+          "nft_payout",
+          "nft_is_approved",
+          "storage_balance_of",
+          "storage_balance_bounds",
+          "get_sale",
         ],
-        viewMethods: ["get_guest", "get_token_ids", "nft_token", "get_sale"],
       },
       marketDeposit: "100000000000000000000000",
       marketId: "market." + contractName,
@@ -1,4 +1,5 @@
 const fs = require('fs');
+const BN = require('bn.js');
 const nearAPI = require('near-api-js');
 const testUtils = require('./test-utils');
 const getConfig = require('../src/config');
@@ -24,13 +25,32 @@ describe('deploy contract ' + contractName, () => {
     let alice, bobId, bob, bobKey, marketAccount;
 
     const market_deposit = parseNearAmount('0.1');
-    const metadata = 'https://media1.tenor.com/images/4c1d96a989150e7019bfbabbebd2ff36/tenor.gif?itemid=20269144'
-    const metadata2 = 'https://media1.tenor.com/images/818161c07948bac34aa7c5f5712ec3d7/tenor.gif?itemid=15065455'
+    const metadata = {
+        title: 'Dancing',
+        media: 'https://media1.tenor.com/images/4c1d96a989150e7019bfbabbebd2ff36/tenor.gif?itemid=20269144',
+    }
+    const metadata2 = {
+        title: 'Waving',
+        media: 'https://media1.tenor.com/images/818161c07948bac34aa7c5f5712ec3d7/tenor.gif?itemid=15065455',
+    }
 
     const tokenIds = [
         'token' + Date.now(),
         'token' + Date.now() + 1,
-        'token' + Date.now() + 2
+        'token' + Date.now() + 2,
+        'token' + Date.now() + 3,
+        'token' + Date.now() + 4,
+        'token' + Date.now() + 5,
+        'token' + Date.now() + 6,
+        'token' + Date.now() + 7,
+        'token' + Date.now() + 8,
+        'token' + Date.now() + 9,
+        'token' + Date.now() + 10,
+        'token' + Date.now() + 11,
+        'token' + Date.now() + 12,
+        'token' + Date.now() + 13,
+        'token' + Date.now() + 14,
+        'token' + Date.now() + 15,
     ]
 
     /// contractAccount.accountId is the token contract and contractAccount is the owner
@@ -41,6 +61,13 @@ describe('deploy contract ' + contractName, () => {
     const guestId = 'guests.' + contractId;
     const marketId = 'market.' + contractId;
 
+    /// token receivers pay for their token set, so fresh accounts register a storage balance first
+    const getReceiver = async () => {
+        const account = await getAccount();
+        await account.functionCall(contractId, 'storage_deposit', {}, GAS, parseNearAmount('0.01'));
+        return account;
+    }
+
 	beforeAll(async () => {
 	    await initContract();
 
@@ -59,7 +86,7 @@ describe('deploy contract ' + contractName, () => {
 		const guestAccount = await createOrInitAccount(guestId, GUESTS_ACCOUNT_SECRET);
 		await guestAccount.addKey(public_key, contractId, contractMethods.changeMethods, parseNearAmount('0.1'));
 		try {
-			await contract.add_guest({ account_id: bobId, public_key }, GAS);
+			await contract.add_guest({ account_id: bobId, public_key }, GAS, parseNearAmount('0.01'));
 		} catch(e) {
 			console.warn(e);
 		}
@@ -94,12 +121,16 @@ describe('deploy contract ' + contractName, () => {
                 beneficiary: alice.accountId,
                 price: parseNearAmount('1')
             })
-        }, GAS, parseNearAmount('0.1001'));
+        }, GAS, parseNearAmount('0.11'));
         const token = await contract.nft_token({ token_id });
         const sale = await alice.viewFunction(marketId, 'get_sale', { token_contract_id: contractId, token_id });
 		console.log('\n\n', sale, '\n\n');
         expect(sale.price).toEqual(parseNearAmount('1'))
         expect(token.owner_id).toEqual(alice.accountId)
+        const approval_id = token.approved_account_ids[marketId]
+        expect(sale.approval_id).toEqual(approval_id)
+        expect(await contract.nft_is_approved({ token_id, approved_account_id: marketId, approval_id })).toEqual(true)
+        expect(await contract.nft_is_approved({ token_id, approved_account_id: marketId, approval_id: approval_id + 1 })).toEqual(false)
 	});
 
 	test('change price', async () => {
@@ -110,14 +141,127 @@ describe('deploy contract ' + contractName, () => {
         expect(sale.price).toEqual(parseNearAmount('2'))
 	});
 
+	test('storage deposit and withdraw', async () => {
+        const before = await contract.storage_balance_of({ account_id: alice.accountId });
+		await alice.functionCall(contractId, 'storage_deposit', {}, GAS, parseNearAmount('0.1'));
+        const after = await contract.storage_balance_of({ account_id: alice.accountId });
+        expect(BigInt(after.available) - BigInt(before.available)).toEqual(BigInt(parseNearAmount('0.1')))
+		await alice.functionCall(contractId, 'storage_withdraw', {}, GAS, 1);
+        const { available } = await contract.storage_balance_of({ account_id: alice.accountId });
+        expect(available).toEqual('0')
+	});
+
+	test('nft approve and revoke are balance neutral for the contract', async () => {
+        const token_id = tokenIds[4]
+        const byteCost = BigInt(parseNearAmount('0.00001'))
+		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+		await alice.functionCall(contractId, 'storage_deposit', {}, GAS, parseNearAmount('0.01'));
+        const before = await contract.storage_balance_of({ account_id: alice.accountId });
+        const { storage_usage: usageBefore } = await contractAccount.state();
+
+        /// NEP-178 asks for at least 1 yoctoNEAR
+        await expect(
+            alice.functionCall(contractId, 'nft_approve', { token_id, account_id: marketId }, GAS)
+        ).rejects.toThrow(/Requires attached deposit of at least 1 yoctoNEAR/)
+
+        /// only 1 yoctoNEAR attached, approval is paid from alice's storage balance
+		await alice.functionCall(contractId, 'nft_approve', { token_id, account_id: marketId }, GAS, 1);
+        const approved = await contract.storage_balance_of({ account_id: alice.accountId });
+        const { storage_usage: usageApproved } = await contractAccount.state();
+        expect(usageApproved).toBeGreaterThan(usageBefore)
+        expect(BigInt(before.available) - BigInt(approved.available)).toEqual(BigInt(usageApproved - usageBefore) * byteCost)
+
+		await alice.functionCall(contractId, 'nft_revoke', { token_id, account_id: marketId }, GAS, 1);
+        const after = await contract.storage_balance_of({ account_id: alice.accountId });
+        const { storage_usage: usageAfter } = await contractAccount.state();
+        expect(usageAfter).toEqual(usageBefore)
+        expect(after).toEqual(before)
+	});
+
+	test('nft approve without storage balance or deposit fails', async () => {
+        const token_id = tokenIds[4]
+		await alice.functionCall(contractId, 'storage_withdraw', {}, GAS, 1);
+        let error
+        try {
+            await alice.functionCall(contractId, 'nft_approve', { token_id, account_id: marketId }, GAS, 1);
+        } catch (e) {
+            error = e
+        }
+        expect(error).toBeDefined()
+        const token = await contract.nft_token({ token_id });
+        expect(token.approved_account_ids).toEqual({})
+	});
+
+	test('nft mint and burn', async () => {
+        const token_id = tokenIds[3]
+		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+        const supply = await contract.nft_total_supply();
+		await alice.functionCall(contractId, 'nft_burn', { token_id }, GAS, 1);
+        const token = await contract.nft_token({ token_id });
+        expect(token).toEqual(null)
+        expect(await contract.nft_total_supply()).toEqual((parseInt(supply) - 1).toString())
+	});
+
+    /// stale listings: the market enforces the approval_id it was given when the sale was added
+
+	test('purchase fails after owner re-approves market', async () => {
+        const token_id = tokenIds[5]
+		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+        await alice.functionCall(contractId, 'nft_approve', {
+            token_id,
+            account_id: marketId,
+            msg: JSON.stringify({
+                beneficiary: alice.accountId,
+                price: parseNearAmount('1')
+            })
+        }, GAS, parseNearAmount('0.11'));
+        /// no msg, market keeps the sale with the old approval_id
+        await alice.functionCall(contractId, 'nft_approve', { token_id, account_id: marketId }, GAS, 1);
+        const sale = await alice.viewFunction(marketId, 'get_sale', { token_contract_id: contractId, token_id });
+        let token = await contract.nft_token({ token_id });
+        expect(token.approved_account_ids[marketId]).toBeGreaterThan(sale.approval_id)
+
+        const buyer = await getAccount();
+		await buyer.functionCall(marketId, 'purchase', { token_contract_id: contractId, token_id }, GAS, parseNearAmount('1'));
+        token = await contract.nft_token({ token_id });
+        expect(token.owner_id).toEqual(alice.accountId)
+        const saleAfter = await alice.viewFunction(marketId, 'get_sale', { token_contract_id: contractId, token_id });
+        expect(saleAfter.processing).toEqual(false)
+	});
+
+	test('purchase fails after owner transfers and new owner approves market', async () => {
+        const token_id = tokenIds[5]
+        const carol = await getReceiver();
+		await alice.functionCall(contractId, 'nft_transfer', { token_id, receiver_id: carol.accountId }, GAS, 1);
+        await carol.functionCall(contractId, 'nft_approve', { token_id, account_id: marketId }, GAS, parseNearAmount('0.01'));
+        const token = await contract.nft_token({ token_id });
+        expect(token.approved_account_ids[marketId]).toBeDefined()
+
+        const buyer = await getAccount();
+		await buyer.functionCall(marketId, 'purchase', { token_contract_id: contractId, token_id }, GAS, parseNearAmount('1'));
+        const tokenAfter = await contract.nft_token({ token_id });
+        expect(tokenAfter.owner_id).toEqual(carol.accountId)
+	});
+
 	test('nft mint', async () => {
         const token_id = tokenIds[0]
-		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+		await alice.functionCall(contractId, 'nft_mint', {
+            token_id,
+            metadata,
+            perpetual_royalties: { [contractId]: 1000 }
+        }, GAS, parseNearAmount('1'));
         const token = await contract.nft_token({ token_id });
-        expect(token.metadata).toEqual(metadata)
+        expect(token.metadata.media).toEqual(metadata.media)
         expect(token.owner_id).toEqual(alice.accountId)
 	});
 
+	test('nft payout', async () => {
+        const token_id = tokenIds[0]
+        const { payout } = await contract.nft_payout({ token_id, balance: parseNearAmount('1'), max_len_payout: 10 });
+        expect(payout[contractId]).toEqual(parseNearAmount('0.1'))
+        expect(payout[alice.accountId]).toEqual(parseNearAmount('0.9'))
+	});
+
 	test('nft transfer to guest', async () => {
         const token_id = tokenIds[0]
 		await alice.functionCall(contractId, 'nft_transfer', { token_id, receiver_id: bobId }, GAS, 1);
@@ -129,8 +273,18 @@ describe('deploy contract ' + contractName, () => {
         const token_id = tokenIds[1]
 		await bob.functionCall(contractId, 'nft_mint_guest', { token_id, metadata }, GAS);
         const token = await contract.nft_token({ token_id });
-        expect(token.metadata).toEqual(metadata)
+        expect(token.metadata.media).toEqual(metadata.media)
         expect(token.owner_id).toEqual(bobId)
+        expect(await contract.is_account_registered({ account_id: bobId })).toEqual(true)
+	});
+
+	test('nft tokens for owner', async () => {
+        const supply = await contract.nft_supply_for_owner({ account_id: bobId });
+        expect(supply).toEqual('2')
+        const tokens = await contract.nft_tokens_for_owner({ account_id: bobId, from_index: '0', limit: 10 });
+        expect(tokens.map(({ token_id }) => token_id).sort()).toEqual([tokenIds[0], tokenIds[1]].sort())
+        const page = await contract.nft_tokens({ from_index: '0', limit: 1 });
+        expect(page.length).toEqual(1)
 	});
 
     /// selling token as guest
@@ -156,14 +310,92 @@ describe('deploy contract ' + contractName, () => {
         expect(sale.owner_id).toEqual(bobId)
 	});
 
+	test('get sales by nft contract and owner', async () => {
+        const sales = await alice.viewFunction(marketId, 'get_sales_by_nft_contract_id', { nft_contract_id: contractId, from_index: '0', limit: 100 });
+        const tokenIdsForSale = sales.map(({ token_id }) => token_id)
+        expect(tokenIdsForSale).toContain(tokenIds[0])
+        const supply = await alice.viewFunction(marketId, 'get_supply_by_nft_contract_id', { nft_contract_id: contractId });
+        expect(parseInt(supply)).toEqual(sales.length)
+        const bobSales = await alice.viewFunction(marketId, 'get_sales_by_owner_id', { account_id: bobId, from_index: '0', limit: 100 });
+        expect(bobSales.map(({ token_id }) => token_id)).toContain(tokenIds[0])
+	});
+
 	test('purchase nft from market', async () => {
         const token_id = tokenIds[0]
+        /// 2.5% marketplace fee for this nft contract
+        await contractAccount.functionCall(marketId, 'set_nft_contract_fee_bps', { nft_contract_id: contractId, fee_bps: 250 }, GAS);
+        const treasury = await alice.viewFunction(marketId, 'get_treasury', {});
+        const guest = await bob.viewFunction(contractId, 'get_guest', { public_key: bobKey });
 		await alice.functionCall(marketId, 'purchase', {
             token_contract_id: contractId,
             token_id
         }, GAS, parseNearAmount('1'));
         const token = await contract.nft_token({ token_id });
         expect(token.owner_id).toEqual(alice.accountId)
+        /// bob is credited what's left of 0.975 after the 10% royalty to contractId
+        const guestAfter = await bob.viewFunction(contractId, 'get_guest', { public_key: bobKey });
+        expect(new BN(guestAfter.balance).sub(new BN(guest.balance)).toString()).toEqual(parseNearAmount('0.8775'))
+        const treasuryAfter = await alice.viewFunction(marketId, 'get_treasury', {});
+        expect(new BN(treasuryAfter).sub(new BN(treasury)).toString()).toEqual(parseNearAmount('0.025'))
+
+        await contractAccount.functionCall(marketId, 'withdraw_treasury', {}, GAS);
+        expect(await alice.viewFunction(marketId, 'get_treasury', {})).toEqual('0')
+        await contractAccount.functionCall(marketId, 'set_nft_contract_fee_bps', { nft_contract_id: contractId }, GAS);
+	});
+
+	test('nft remove sale guest', async () => {
+        const token_id = tokenIds[1]
+		await bob.functionCall(contractId, 'nft_add_sale_guest', {
+            token_id,
+            price: parseNearAmount('1'),
+            market_id: marketId,
+            market_deposit
+        }, GAS);
+        const guest = await bob.viewFunction(contractId, 'get_guest', { public_key: bobKey });
+		await bob.functionCall(contractId, 'nft_remove_sale_guest', { token_id, market_id: marketId }, GAS);
+        const token = await contract.nft_token({ token_id });
+        expect(token.approved_account_ids[marketId]).toBeUndefined()
+        const sales = await alice.viewFunction(marketId, 'get_sales_by_owner_id', { account_id: bobId, from_index: '0', limit: 100 });
+        expect(sales.map(({ token_id }) => token_id)).not.toContain(token_id)
+        const guestAfter = await bob.viewFunction(contractId, 'get_guest', { public_key: bobKey });
+        expect(new BN(guestAfter.balance).gt(new BN(guest.balance))).toEqual(true)
+	});
+
+	test('nft transfer guest', async () => {
+        const token_id = tokenIds[1]
+		await bob.functionCall(contractId, 'nft_transfer_guest', { receiver_id: alice.accountId, token_id }, GAS);
+        const token = await contract.nft_token({ token_id });
+        expect(token.owner_id).toEqual(alice.accountId)
+        const guest = await bob.viewFunction(contractId, 'get_guest', { public_key: bobKey });
+        expect(guest.transfers).toEqual(1)
+	});
+
+	test('guest policy', async () => {
+        const policy = await contractAccount.viewFunction(contractId, 'get_guest_policy', {});
+        expect(policy.mint_limit).toEqual(3)
+        await contractAccount.functionCall(contractId, 'set_guest_policy', { guest_policy: { ...policy, sale_limit: 5 } }, GAS);
+        expect((await contractAccount.viewFunction(contractId, 'get_guest_policy', {})).sale_limit).toEqual(5)
+        await contractAccount.functionCall(contractId, 'set_guest_policy', { guest_policy: policy }, GAS);
+        const guest = await bob.viewFunction(contractId, 'get_guest', { public_key: bobKey });
+        expect(guest.mints).toEqual(1)
+        expect(guest.sales).toEqual(2)
+	});
+
+	test('withdraw guest to missing account restores balance and tokens', async () => {
+        const token_id = tokenIds[14]
+		await bob.functionCall(contractId, 'nft_mint_guest', { token_id, metadata }, GAS);
+        const guest = await bob.viewFunction(contractId, 'get_guest', { public_key: bobKey });
+        const account_id = 'missing' + Date.now() + '.' + contractId
+        /// registered with the nft contract so it can receive tokens, but the NEAR account doesn't exist
+        await alice.functionCall(contractId, 'storage_deposit', { account_id }, GAS, parseNearAmount('0.01'));
+		await bob.functionCall(contractId, 'withdraw_guest_to', {
+            account_id,
+            include_tokens: true
+        }, GAS);
+        const guestAfter = await bob.viewFunction(contractId, 'get_guest', { public_key: bobKey });
+        expect(guestAfter.balance).toEqual(guest.balance)
+        const token = await contract.nft_token({ token_id });
+        expect(token.owner_id).toEqual(bobId)
 	});
 
 	test('get guest', async () => {
@@ -171,7 +403,27 @@ describe('deploy contract ' + contractName, () => {
         console.log('\n\n', guest, '\n\n');
 	});
 
+	test('remove guest refuses with tokens and transfers them', async () => {
+        const token_id = tokenIds[12]
+        const public_key = KeyPair.fromRandom('ed25519').publicKey.toString()
+        const account_id = 'tmp' + Date.now() + '.' + contractId
+		await contract.add_guest({ account_id, public_key }, GAS, parseNearAmount('0.01'));
+		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+		await alice.functionCall(contractId, 'nft_transfer', { token_id, receiver_id: account_id }, GAS, 1);
+        await expect(
+            contract.remove_guest({ public_key }, GAS)
+        ).rejects.toThrow(/Guest still owns 1 tokens/)
+        /// true once the guest has no tokens left and the record is removed
+        expect(await contract.remove_guest({ public_key, mode: { transfer: { receiver_id: alice.accountId } } }, GAS)).toEqual(true)
+        const token = await contract.nft_token({ token_id });
+        expect(token.owner_id).toEqual(alice.accountId)
+        expect(await contract.nft_supply_for_owner({ account_id })).toEqual('0')
+	});
+
 	test('upgrade guest self', async () => {
+        /// 0.8775 from the sale of tokenIds[0] plus the listing storage refunded for tokenIds[1]
+        const guest = await bob.viewFunction(contractId, 'get_guest', { public_key: bobKey });
+        expect(new BN(guest.balance).gt(new BN(parseNearAmount('0.8775')))).toEqual(true)
 		const keyPair = KeyPair.fromRandom('ed25519');
 		const keyPair2 = KeyPair.fromRandom('ed25519');
 		const public_key = keyPair.publicKey.toString();
@@ -185,8 +437,282 @@ describe('deploy contract ' + contractName, () => {
         console.log('\n\n', result, '\n\n');
 		/// update account and contract for bob (bob now pays gas)
 		const balance = await testUtils.getAccountBalance(bobId);
-		expect(balance.total).toEqual(parseNearAmount('0.9'));
+		/// minus the 0.1 sponsor fee of the default guest policy
+		expect(balance.total).toEqual(new BN(guest.balance).sub(new BN(parseNearAmount('0.1'))).toString());
 		
 	});
 
+    /// english auctions, the sale price is the reserve
+
+	test('auction bid and settle', async () => {
+        const token_id = tokenIds[6]
+		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+        const end_at = ((Date.now() + 15000) * 1000000).toString()
+        await alice.functionCall(contractId, 'nft_approve', {
+            token_id,
+            account_id: marketId,
+            msg: JSON.stringify({
+                beneficiary: alice.accountId,
+                price: parseNearAmount('1'),
+                auction: { min_increment: parseNearAmount('0.1'), end_at, extension: '0' }
+            })
+        }, GAS, parseNearAmount('0.11'));
+
+        const bidder1 = await getReceiver();
+        const bidder2 = await getReceiver();
+		await bidder1.functionCall(marketId, 'bid', { token_contract_id: contractId, token_id }, GAS, parseNearAmount('1'));
+        await expect(
+            bidder2.functionCall(marketId, 'bid', { token_contract_id: contractId, token_id }, GAS, parseNearAmount('1.05'))
+        ).rejects.toThrow(/Bid must be at least/)
+		await bidder2.functionCall(marketId, 'bid', { token_contract_id: contractId, token_id }, GAS, parseNearAmount('1.1'));
+        const sale = await alice.viewFunction(marketId, 'get_sale', { token_contract_id: contractId, token_id });
+        expect(sale.auction.bidder_id).toEqual(bidder2.accountId)
+
+        await new Promise((r) => setTimeout(r, 16000));
+		await alice.functionCall(marketId, 'settle_auction', { token_contract_id: contractId, token_id }, GAS);
+        const token = await contract.nft_token({ token_id });
+        expect(token.owner_id).toEqual(bidder2.accountId)
+	});
+
+	test('dutch auction price declines and buyer gets the difference back', async () => {
+        const token_id = tokenIds[7]
+		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+        const end_at = ((Date.now() + 10000) * 1000000).toString()
+        await alice.functionCall(contractId, 'nft_approve', {
+            token_id,
+            account_id: marketId,
+            msg: JSON.stringify({
+                beneficiary: alice.accountId,
+                price: parseNearAmount('2'),
+                dutch_auction: { end_price: parseNearAmount('1'), end_at }
+            })
+        }, GAS, parseNearAmount('0.11'));
+
+        await new Promise((r) => setTimeout(r, 11000));
+        const sale = await alice.viewFunction(marketId, 'get_sale', { token_contract_id: contractId, token_id });
+        expect(sale.price).toEqual(parseNearAmount('1'))
+        expect(sale.dutch_auction.start_price).toEqual(parseNearAmount('2'))
+
+        const buyer = await getReceiver();
+        const balanceBefore = await getAccountBalance(buyer.accountId);
+		await buyer.functionCall(marketId, 'purchase', { token_contract_id: contractId, token_id }, GAS, parseNearAmount('2'));
+        const balanceAfter = await getAccountBalance(buyer.accountId);
+        const token = await contract.nft_token({ token_id });
+        expect(token.owner_id).toEqual(buyer.accountId)
+        /// paid the floor plus gas, not the start price
+        expect(new BN(balanceBefore.total).sub(new BN(balanceAfter.total)).lt(new BN(parseNearAmount('1.1')))).toEqual(true)
+	});
+
+	test('expired sale cannot be purchased and is pruned', async () => {
+        const token_id = tokenIds[10]
+        const burned_token_id = tokenIds[15]
+		await alice.functionCall(contractId, 'nft_mint', { token_id: burned_token_id, metadata }, GAS, parseNearAmount('1'));
+        await alice.functionCall(contractId, 'nft_approve', {
+            token_id: burned_token_id,
+            account_id: marketId,
+            msg: JSON.stringify({
+                beneficiary: alice.accountId,
+                price: parseNearAmount('1')
+            })
+        }, GAS, parseNearAmount('0.11'));
+        /// nft_is_approved fails for a burned token, so its sale is stale too
+		await alice.functionCall(contractId, 'nft_burn', { token_id: burned_token_id }, GAS, 1);
+		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+        const expires_at = ((Date.now() + 5000) * 1000000).toString()
+        await alice.functionCall(contractId, 'nft_approve', {
+            token_id,
+            account_id: marketId,
+            msg: JSON.stringify({
+                beneficiary: alice.accountId,
+                price: parseNearAmount('1'),
+                expires_at
+            })
+        }, GAS, parseNearAmount('0.11'));
+        await new Promise((r) => setTimeout(r, 6000));
+
+        const buyer = await getAccount();
+        await expect(
+            buyer.functionCall(marketId, 'purchase', { token_contract_id: contractId, token_id }, GAS, parseNearAmount('1'))
+        ).rejects.toThrow(/Sale has expired/)
+
+        const supply = await alice.viewFunction(marketId, 'get_supply_sales', {});
+        /// back to front, pruning moves the last sale into the freed index
+        for (let from_index = Math.floor((parseInt(supply) - 1) / 10) * 10; from_index >= 0; from_index -= 10) {
+		    await buyer.functionCall(marketId, 'prune_sales', { from_index: from_index.toString(), limit: 10 }, GAS);
+        }
+        const sales = await alice.viewFunction(marketId, 'get_sales_by_nft_contract_id', { nft_contract_id: contractId, from_index: '0', limit: 100 });
+        expect(sales.map(({ token_id }) => token_id)).not.toContain(token_id)
+        expect(sales.map(({ token_id }) => token_id)).not.toContain(burned_token_id)
+	});
+
+	test('market storage balance covers listings without attached deposit', async () => {
+        const token_id = tokenIds[11]
+		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+        const { min } = await alice.viewFunction(marketId, 'storage_balance_bounds', {});
+        await alice.functionCall(marketId, 'storage_deposit', {}, GAS, min);
+        const balance = await alice.viewFunction(marketId, 'storage_balance_of', { account_id: alice.accountId });
+        expect(new BN(balance.available).gte(new BN(min))).toEqual(true)
+
+        await alice.functionCall(contractId, 'nft_approve', {
+            token_id,
+            account_id: marketId,
+            msg: JSON.stringify({
+                beneficiary: alice.accountId,
+                price: parseNearAmount('1')
+            })
+        }, GAS, parseNearAmount('0.01'));
+        const sale = await alice.viewFunction(marketId, 'get_sale', { token_contract_id: contractId, token_id });
+        expect(sale.owner_id).toEqual(alice.accountId)
+
+		await alice.functionCall(marketId, 'storage_withdraw', {}, GAS, 1);
+        const balanceAfter = await alice.viewFunction(marketId, 'storage_balance_of', { account_id: alice.accountId });
+        expect(balanceAfter.available).toEqual('0')
+        const supply = await alice.viewFunction(marketId, 'get_supply_by_owner_id', { account_id: alice.accountId });
+        expect(balanceAfter.total).toEqual(new BN(min).mul(new BN(supply)).toString())
+	});
+
+	test('market storage registration requires one listing', async () => {
+        const account = await getAccount();
+        const { min } = await account.viewFunction(marketId, 'storage_balance_bounds', {});
+        await expect(
+            account.functionCall(marketId, 'storage_deposit', {}, GAS, new BN(min).sub(new BN('1')).toString())
+        ).rejects.toThrow(/Requires a deposit of at least/)
+        expect(await account.viewFunction(marketId, 'storage_balance_of', { account_id: account.accountId })).toEqual(null)
+	});
+
+	test('purchase with a whitelisted fungible token', async () => {
+        const token_id = tokenIds[13]
+        const ftId = 'ft.' + contractId;
+		const ftAccount = await createOrInitAccount(ftId, GUESTS_ACCOUNT_SECRET);
+        const state = await ftAccount.state()
+        if (state.code_hash === '11111111111111111111111111111111') {
+            const contractBytes = fs.readFileSync('./out/test_ft.wasm');
+            const actions = [
+                deployContract(contractBytes),
+                functionCall('new', { owner_id: contractId, total_supply: parseNearAmount('1000000') }, GAS)
+            ]
+            await ftAccount.signAndSendTransaction(ftId, actions)
+        }
+        await contractAccount.functionCall(marketId, 'add_ft_token_ids', { ft_token_ids: [ftId] }, GAS);
+        expect(await alice.viewFunction(marketId, 'supported_ft_token_ids', {})).toContain(ftId)
+
+        const buyer = await getReceiver();
+        await buyer.functionCall(ftId, 'storage_deposit', {}, GAS, parseNearAmount('0.01'));
+        await alice.functionCall(ftId, 'storage_deposit', {}, GAS, parseNearAmount('0.01'));
+        await contractAccount.functionCall(ftId, 'ft_transfer', { receiver_id: buyer.accountId, amount: parseNearAmount('10') }, GAS, 1);
+
+		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+        await alice.functionCall(contractId, 'nft_approve', {
+            token_id,
+            account_id: marketId,
+            msg: JSON.stringify({
+                beneficiary: alice.accountId,
+                price: parseNearAmount('1'),
+                ft_prices: { [ftId]: parseNearAmount('5') }
+            })
+        }, GAS, parseNearAmount('0.11'));
+
+        const aliceBalance = await alice.viewFunction(ftId, 'ft_balance_of', { account_id: alice.accountId });
+        /// overpaying, the market returns what the sale didn't use
+		await buyer.functionCall(ftId, 'ft_transfer_call', {
+            receiver_id: marketId,
+            amount: parseNearAmount('6'),
+            msg: JSON.stringify({ token_contract_id: contractId, token_id })
+        }, '300000000000000', 1);
+        const token = await contract.nft_token({ token_id });
+        expect(token.owner_id).toEqual(buyer.accountId)
+        expect(await alice.viewFunction(ftId, 'ft_balance_of', { account_id: buyer.accountId })).toEqual(parseNearAmount('5'))
+        const aliceBalanceAfter = await alice.viewFunction(ftId, 'ft_balance_of', { account_id: alice.accountId });
+        expect(new BN(aliceBalanceAfter).sub(new BN(aliceBalance)).toString()).toEqual(parseNearAmount('5'))
+
+        await contractAccount.functionCall(marketId, 'remove_ft_token_ids', { ft_token_ids: [ftId] }, GAS);
+        expect(await alice.viewFunction(marketId, 'supported_ft_token_ids', {})).not.toContain(ftId)
+	});
+
+    /// offers on tokens that aren't listed
+
+	test('make and accept offer', async () => {
+        const token_id = tokenIds[8]
+		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+        const bidder = await getReceiver();
+		await bidder.functionCall(marketId, 'make_offer', { token_contract_id: contractId, token_id }, GAS, parseNearAmount('0.5'));
+        const offers = await alice.viewFunction(marketId, 'get_offers_by_token', { token_contract_id: contractId, token_id });
+        expect(offers.map(({ bidder_id }) => bidder_id)).toEqual([bidder.accountId])
+        const bidderOffers = await alice.viewFunction(marketId, 'get_offers_by_bidder', { bidder_id: bidder.accountId });
+        expect(bidderOffers[0].amount).toEqual(parseNearAmount('0.5'))
+
+        /// accepted offers pay the marketplace fee like purchases
+        await contractAccount.functionCall(marketId, 'set_nft_contract_fee_bps', { nft_contract_id: contractId, fee_bps: 250 }, GAS);
+        const treasury = await alice.viewFunction(marketId, 'get_treasury', {});
+        await alice.functionCall(contractId, 'nft_approve', {
+            token_id,
+            account_id: marketId,
+            msg: JSON.stringify({ accept_offer: { bidder_id: bidder.accountId } })
+        }, GAS, parseNearAmount('0.01'));
+        const token = await contract.nft_token({ token_id });
+        expect(token.owner_id).toEqual(bidder.accountId)
+        const treasuryAfter = await alice.viewFunction(marketId, 'get_treasury', {});
+        expect(new BN(treasuryAfter).sub(new BN(treasury)).toString()).toEqual(parseNearAmount('0.0125'))
+        await contractAccount.functionCall(marketId, 'set_nft_contract_fee_bps', { nft_contract_id: contractId }, GAS);
+        const offer = await alice.viewFunction(marketId, 'get_offer', { token_contract_id: contractId, token_id, bidder_id: bidder.accountId });
+        expect(offer).toEqual(null)
+	});
+
+	test('make, partially fill and cancel collection offer', async () => {
+        const token_id = tokenIds[9]
+		await alice.functionCall(contractId, 'nft_mint', { token_id, metadata }, GAS, parseNearAmount('1'));
+        const bidder = await getReceiver();
+		await bidder.functionCall(marketId, 'make_collection_offer', {
+            token_contract_id: contractId,
+            price_per_token: parseNearAmount('0.5'),
+            quantity: 2,
+        }, GAS, parseNearAmount('1'));
+
+        await alice.functionCall(contractId, 'nft_approve', {
+            token_id,
+            account_id: marketId,
+            msg: JSON.stringify({ fill_collection_offer: { bidder_id: bidder.accountId } })
+        }, GAS, parseNearAmount('0.01'));
+        const token = await contract.nft_token({ token_id });
+        expect(token.owner_id).toEqual(bidder.accountId)
+        let offer = await alice.viewFunction(marketId, 'get_collection_offer', { token_contract_id: contractId, bidder_id: bidder.accountId });
+        expect(offer.quantity).toEqual(1)
+
+		await bidder.functionCall(marketId, 'cancel_collection_offer', { token_contract_id: contractId }, GAS);
+        offer = await alice.viewFunction(marketId, 'get_collection_offer', { token_contract_id: contractId, bidder_id: bidder.accountId });
+        expect(offer).toEqual(null)
+	});
+
+    /// launchpad factory deploys nft-simple collections for creators
+
+	test('factory create collection', async () => {
+        const factoryId = 'factory.' + contractId;
+		const factoryAccount = await createOrInitAccount(factoryId, GUESTS_ACCOUNT_SECRET);
+        const state = await factoryAccount.state()
+        if (state.code_hash === '11111111111111111111111111111111') {
+            const contractBytes = fs.readFileSync('./out/factory.wasm');
+            const actions = [
+                deployContract(contractBytes),
+                functionCall('new', { owner_id: contractId }, GAS)
+            ]
+            await factoryAccount.signAndSendTransaction(factoryId, actions)
+        }
+        const creator = await getAccount();
+        const name = 'c' + Date.now()
+        const deposit = await creator.viewFunction(factoryId, 'get_min_collection_deposit', {});
+		await creator.functionCall(factoryId, 'create_collection', {
+            name,
+            symbol: 'COL',
+            royalties: { [creator.accountId]: 500 },
+            minting_policy: 'owner_only',
+        }, GAS, deposit);
+        const collectionId = name + '.' + factoryId
+        const collection = await creator.viewFunction(factoryId, 'get_collection', { collection_id: collectionId });
+        expect(collection.creator_id).toEqual(creator.accountId)
+        const collectionMetadata = await creator.viewFunction(collectionId, 'nft_metadata', {});
+        expect(collectionMetadata.symbol).toEqual('COL')
+        const collections = await creator.viewFunction(factoryId, 'get_collections', { from_index: '0', limit: 100 });
+        expect(collections.map(({ collection_id }) => collection_id)).toContain(collectionId)
+	});
+
 });
\ No newline at end of file